@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::Hasher as StdHasher;
 use std::panic::panic_any;
@@ -19,7 +20,7 @@ use merkletree::{
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
-use crate::types::{Domain, HashFunction, Hasher};
+use crate::types::{Domain, HashFunction, Hasher, TryFromBytesError};
 
 #[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Blake2sHasher {}
@@ -127,6 +128,14 @@ impl From<Blake2sDomain> for Fr {
     }
 }
 
+impl TryFrom<&[u8]> for Blake2sDomain {
+    type Error = TryFromBytesError;
+
+    fn try_from(raw: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(raw).map_err(|_| TryFromBytesError { len: raw.len() })
+    }
+}
+
 impl Domain for Blake2sDomain {
     fn into_bytes(&self) -> Vec<u8> {
         self.0.to_vec()
@@ -0,0 +1,105 @@
+//! Field arithmetic on the domains whose elements are field elements, for code which would
+//! otherwise convert into `Fr` and back around every addition or subtraction.
+//!
+//! Each operation converts both operands into `Fr`, which panics for elements which are not
+//! canonical field elements. Poseidon outputs always are; SHA-256 and Blake2s digests are only
+//! trimmed to 254 bits, so a `Sha256Domain` built from arbitrary bytes may not be, and must be
+//! validated first (e.g. with `Fr::from_repr_vartime`).
+
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+
+use blstrs::Scalar as Fr;
+
+macro_rules! impl_field_ops {
+    ($domain:ty) => {
+        impl Add for $domain {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                (Fr::from(self) + Fr::from(other)).into()
+            }
+        }
+
+        impl AddAssign for $domain {
+            fn add_assign(&mut self, other: Self) {
+                *self = *self + other;
+            }
+        }
+
+        impl Sub for $domain {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                (Fr::from(self) - Fr::from(other)).into()
+            }
+        }
+
+        impl SubAssign for $domain {
+            fn sub_assign(&mut self, other: Self) {
+                *self = *self - other;
+            }
+        }
+    };
+}
+
+#[cfg(feature = "poseidon")]
+impl_field_ops!(crate::poseidon::PoseidonDomain);
+#[cfg(feature = "sha256")]
+impl_field_ops!(crate::sha256::Sha256Domain);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::Domain;
+
+    const TEST_SEED: [u8; 16] = [
+        0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
+        0xe5,
+    ];
+
+    fn check_ops<D>()
+    where
+        D: Domain + Add<Output = D> + Sub<Output = D> + AddAssign + SubAssign,
+    {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        for _ in 0..16 {
+            let key = Fr::random(&mut *rng);
+            let value = Fr::random(&mut *rng);
+
+            // Encoding is field addition of the key, decoding subtracts it again.
+            let encoded = D::from(key) + D::from(value);
+            assert_eq!(encoded, D::from(key + value));
+            assert_eq!(encoded - D::from(key), D::from(value));
+
+            let mut assigned = D::from(key);
+            assigned += D::from(value);
+            assert_eq!(assigned, encoded);
+            assigned -= D::from(key);
+            assert_eq!(assigned, D::from(value));
+        }
+
+        // Results wrap around the modulus like the field operations do.
+        assert_eq!(
+            D::from(Fr::zero()) - D::from(Fr::one()),
+            D::from(-Fr::one())
+        );
+    }
+
+    #[cfg(feature = "poseidon")]
+    #[test]
+    fn test_poseidon_domain_ops() {
+        check_ops::<crate::poseidon::PoseidonDomain>();
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn test_sha256_domain_ops() {
+        check_ops::<crate::sha256::Sha256Domain>();
+    }
+}
@@ -6,6 +6,8 @@
 
 #[cfg(feature = "blake2s")]
 pub mod blake2s;
+#[cfg(feature = "domain-ops")]
+mod domain_ops;
 #[cfg(feature = "poseidon")]
 pub mod poseidon;
 #[cfg(feature = "poseidon")]
@@ -13,6 +15,8 @@ mod poseidon_types;
 #[cfg(feature = "sha256")]
 pub mod sha256;
 
+#[cfg(test)]
+mod test_vectors;
 mod types;
 
 pub use self::types::*;
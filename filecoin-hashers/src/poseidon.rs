@@ -1,8 +1,9 @@
-use std::cmp::Ordering;
+use std::cmp::{max, Ordering};
+use std::convert::TryFrom;
 use std::hash::{Hash as StdHash, Hasher as StdHasher};
 use std::panic::panic_any;
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use bellperson::{
     gadgets::{boolean::Boolean, num::AllocatedNum},
     ConstraintSystem, SynthesisError,
@@ -10,6 +11,7 @@ use bellperson::{
 use blstrs::Scalar as Fr;
 use ff::{Field, PrimeField};
 use generic_array::typenum::{marker_traits::Unsigned, U2};
+use lazy_static::lazy_static;
 use merkletree::{
     hash::{Algorithm as LightAlgorithm, Hashable},
     merkle::Element,
@@ -19,8 +21,9 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
 use crate::types::{
-    Domain, HashFunction, Hasher, PoseidonArity, PoseidonMDArity, POSEIDON_CONSTANTS_16,
-    POSEIDON_CONSTANTS_2, POSEIDON_CONSTANTS_4, POSEIDON_CONSTANTS_8, POSEIDON_MD_CONSTANTS,
+    Domain, HashFunction, Hasher, PoseidonArity, PoseidonMDArity, TryFromBytesError,
+    POSEIDON_CONSTANTS_16, POSEIDON_CONSTANTS_2, POSEIDON_CONSTANTS_4, POSEIDON_CONSTANTS_8,
+    POSEIDON_MD_CONSTANTS,
 };
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -85,6 +88,8 @@ impl Default for PoseidonDomain {
     }
 }
 
+/// Orders domain elements by their little-endian byte representation, which is stable and cheap
+/// but is not the numeric order of the field elements. Use `PoseidonDomain::cmp_by_value` for that.
 impl Ord for PoseidonDomain {
     #[inline(always)]
     fn cmp(&self, other: &PoseidonDomain) -> Ordering {
@@ -106,6 +111,57 @@ impl AsRef<[u8]> for PoseidonDomain {
     }
 }
 
+impl TryFrom<&[u8]> for PoseidonDomain {
+    type Error = TryFromBytesError;
+
+    fn try_from(raw: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(raw).map_err(|_| TryFromBytesError { len: raw.len() })
+    }
+}
+
+impl PoseidonDomain {
+    /// Borrows the little-endian bytes of the field element. Prefer this over
+    /// `Domain::into_bytes`, which copies them into a new `Vec`.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Compares the field elements represented by `self` and `other` as integers, unlike `Ord`,
+    /// which compares their little-endian bytes.
+    pub fn cmp_by_value(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+
+    /// Splits `bytes` into 32-byte domain elements, checking that each one is a canonical
+    /// encoding of a field element. On failure, the error names the index of the first invalid
+    /// element.
+    pub fn from_bytes_batch(bytes: &[u8]) -> anyhow::Result<Vec<Self>> {
+        let byte_len = PoseidonDomain::byte_len();
+        ensure!(
+            bytes.len() % byte_len == 0,
+            "byte length {} is not a multiple of {}",
+            bytes.len(),
+            byte_len
+        );
+
+        bytes
+            .chunks_exact(byte_len)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut repr = <Fr as PrimeField>::Repr::default();
+                repr.copy_from_slice(chunk);
+                ensure!(
+                    Fr::from_repr_vartime(repr).is_some(),
+                    "element {} is not a canonical field element",
+                    i
+                );
+                Ok(PoseidonDomain(repr))
+            })
+            .collect()
+    }
+}
+
 impl Domain for PoseidonDomain {
     fn into_bytes(&self) -> Vec<u8> {
         self.0.to_vec()
@@ -161,7 +217,11 @@ impl StdHasher for PoseidonFunction {
 
     #[inline]
     fn finish(&self) -> u64 {
-        unimplemented!()
+        // The low 8 bytes of the digest are enough for bucketing.
+        let repr = self.0.to_repr();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&repr[..8]);
+        u64::from_le_bytes(bytes)
     }
 }
 
@@ -178,6 +238,18 @@ fn shared_hash(data: &[u8]) -> PoseidonDomain {
     shared_hash_frs(&preimage).into()
 }
 
+/// Like `shared_hash_frs`, but accepts any number of elements: up to 16 are zero padded to the
+/// next supported arity, more are hashed with `hash_md`.
+fn shared_hash_frs_padded(mut preimage: Vec<Fr>) -> Fr {
+    if preimage.len() <= 16 {
+        preimage.resize(max(2, preimage.len().next_power_of_two()), Fr::zero());
+        shared_hash_frs(&preimage)
+    } else {
+        let preimage: Vec<PoseidonDomain> = preimage.into_iter().map(Into::into).collect();
+        PoseidonFunction::hash_md(&preimage).into()
+    }
+}
+
 fn shared_hash_frs(preimage: &[Fr]) -> Fr {
     match preimage.len() {
         2 => {
@@ -209,6 +281,50 @@ impl HashFunction<PoseidonDomain> for PoseidonFunction {
         shared_hash(data)
     }
 
+    /// Poseidon hashes field elements rather than bytes, so pack the bits least significant first
+    /// into elements of `Fr::CAPACITY` bits, which are always canonical, the last one padded with
+    /// zero bits. The elements are then zero padded to a supported arity, or hashed with
+    /// `hash_md` beyond it. There is no matching gadget, as `hash_circuit` is unimplemented.
+    fn hash_bits(bits: &[bool]) -> PoseidonDomain {
+        let preimage = bits
+            .chunks(Fr::CAPACITY as usize)
+            .map(|chunk| {
+                let mut repr = [0u8; 32];
+                for (i, bit) in chunk.iter().enumerate() {
+                    repr[i / 8] |= (*bit as u8) << (i % 8);
+                }
+                Fr::from_repr_vartime(repr).expect("from_repr failure")
+            })
+            .collect();
+        shared_hash_frs_padded(preimage).into()
+    }
+
+    /// Poseidon has no arity for an empty preimage, so the empty hash is that of two zero
+    /// elements.
+    fn hash_empty() -> PoseidonDomain {
+        lazy_static! {
+            static ref HASH_EMPTY: PoseidonDomain =
+                PoseidonFunction::hash2(&PoseidonDomain::default(), &PoseidonDomain::default());
+        }
+        *HASH_EMPTY
+    }
+
+    /// Reuses a single hasher, and so its state allocation, for the whole layer.
+    fn hash_layer(pairs: &[(PoseidonDomain, PoseidonDomain)]) -> Vec<PoseidonDomain> {
+        let mut p = Poseidon::new(&*POSEIDON_CONSTANTS_2);
+        pairs
+            .iter()
+            .map(|(left, right)| {
+                p.reset();
+                for node in &[left, right] {
+                    let fr = Fr::from_repr_vartime(node.0).expect("from_repr failure");
+                    p.input(fr).expect("too many inputs for arity 2");
+                }
+                p.hash().into()
+            })
+            .collect()
+    }
+
     fn hash2(a: &PoseidonDomain, b: &PoseidonDomain) -> PoseidonDomain {
         let mut p =
             Poseidon::new_with_preimage(&[(*a).into(), (*b).into()][..], &*POSEIDON_CONSTANTS_2);
@@ -310,6 +426,35 @@ impl HashFunction<PoseidonDomain> for PoseidonFunction {
     }
 }
 
+impl PoseidonFunction {
+    /// Like `LightAlgorithm::node`, but returns an error instead of panicking when either child is
+    /// not a canonical field element.
+    pub fn try_node(left: PoseidonDomain, right: PoseidonDomain) -> anyhow::Result<PoseidonDomain> {
+        Self::try_multi_node(&[left, right])
+    }
+
+    /// Like `LightAlgorithm::multi_node`, but returns an error naming the first child which is not
+    /// a canonical field element instead of panicking.
+    pub fn try_multi_node(parts: &[PoseidonDomain]) -> anyhow::Result<PoseidonDomain> {
+        ensure!(
+            matches!(parts.len(), 2 | 4 | 8 | 16),
+            "unsupported arity {}",
+            parts.len()
+        );
+
+        let preimage = parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                Fr::from_repr_vartime(part.0)
+                    .with_context(|| format!("child {} is not a canonical field element", i))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(shared_hash_frs(&preimage).into())
+    }
+}
+
 impl LightAlgorithm<PoseidonDomain> for PoseidonFunction {
     #[inline]
     fn hash(&mut self) -> PoseidonDomain {
@@ -520,6 +665,19 @@ mod tests {
         assert_eq!(val, val_back);
     }
 
+    #[test]
+    fn test_hash2_bytes() {
+        let a = PoseidonDomain(Fr::one().to_repr());
+        let b = PoseidonDomain(u64s_to_u8s([1, 2, 3, 4]));
+
+        let hashed =
+            PoseidonFunction::hash2_bytes(AsRef::<[u8]>::as_ref(&a), AsRef::<[u8]>::as_ref(&b))
+                .expect("hash2_bytes failure");
+        assert_eq!(hashed, PoseidonFunction::hash2(&a, &b));
+
+        assert!(PoseidonFunction::hash2_bytes(&[0u8; 31], AsRef::<[u8]>::as_ref(&b)).is_err());
+    }
+
     #[test]
     fn test_hash_md() {
         // let arity = PoseidonMDArity::to_usize();
@@ -568,4 +726,206 @@ mod tests {
             circuit_hashed.get_value().expect("get_value failure")
         );
     }
+
+    #[test]
+    fn test_finish() {
+        let finish = |preimage: &[u8]| {
+            let mut h = PoseidonFunction::default();
+            h.write(preimage);
+            h.finish()
+        };
+
+        let one = Fr::one().to_repr();
+        let two = (Fr::one() + Fr::one()).to_repr();
+        let one_two = [one, two].concat();
+        let two_one = [two, one].concat();
+
+        // The digest is the low 8 bytes of the hash of the bytes written.
+        let digest = shared_hash(&one_two);
+        let mut expected = [0u8; 8];
+        expected.copy_from_slice(&digest.0[..8]);
+        assert_eq!(finish(&one_two), u64::from_le_bytes(expected));
+
+        assert_eq!(finish(&one_two), finish(&one_two));
+        assert_ne!(finish(&one_two), finish(&two_one));
+    }
+
+    #[test]
+    fn test_try_from_slice() {
+        let domain = PoseidonDomain(Fr::one().to_repr());
+        let bytes = domain.into_bytes();
+
+        let parsed = PoseidonDomain::try_from(bytes.as_slice()).expect("try_from failure");
+        assert_eq!(parsed, domain);
+
+        for len in &[0, 31, 33] {
+            let err = PoseidonDomain::try_from(vec![0u8; *len].as_slice())
+                .expect_err("invalid length accepted");
+            assert_eq!(err, TryFromBytesError { len: *len });
+        }
+    }
+
+    #[test]
+    fn test_hash_empty() {
+        let empty = PoseidonFunction::hash_empty();
+        assert_eq!(empty, PoseidonFunction::hash_empty());
+        assert_eq!(
+            empty,
+            PoseidonFunction::hash2(&PoseidonDomain::default(), &PoseidonDomain::default())
+        );
+    }
+
+    #[test]
+    fn test_cmp_by_value() {
+        let one: PoseidonDomain = Fr::from(1u64).into();
+        let two_five_six: PoseidonDomain = Fr::from(256u64).into();
+
+        // The first byte of 1 is larger than the first byte of 256.
+        assert_eq!(one.cmp(&two_five_six), Ordering::Greater);
+        assert_eq!(one.cmp_by_value(&two_five_six), Ordering::Less);
+        assert_eq!(one.cmp_by_value(&one), Ordering::Equal);
+
+        let mut values: Vec<PoseidonDomain> =
+            (0..1000u64).rev().map(|n| Fr::from(n).into()).collect();
+        values.push((-Fr::one()).into());
+        values.sort_by(PoseidonDomain::cmp_by_value);
+        let expected: Vec<PoseidonDomain> = (0..1000u64)
+            .map(|n| Fr::from(n).into())
+            .chain(std::iter::once((-Fr::one()).into()))
+            .collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_try_multi_node() {
+        let mut a = PoseidonFunction::default();
+        let parts: Vec<PoseidonDomain> = (0..4u64).map(|n| Fr::from(n).into()).collect();
+
+        assert_eq!(
+            PoseidonFunction::try_multi_node(&parts).expect("try_multi_node failed"),
+            a.multi_node(&parts, 0)
+        );
+        a.reset();
+        assert_eq!(
+            PoseidonFunction::try_node(parts[0], parts[1]).expect("try_node failed"),
+            a.node(parts[0], parts[1], 0)
+        );
+
+        let mut non_canonical = parts.clone();
+        non_canonical[2] = PoseidonDomain([0xff; 32]);
+        let err = PoseidonFunction::try_multi_node(&non_canonical)
+            .expect_err("non-canonical child accepted");
+        assert!(err.to_string().contains("child 2"), "{}", err);
+
+        assert!(PoseidonFunction::try_node(parts[0], PoseidonDomain([0xff; 32])).is_err());
+        assert!(PoseidonFunction::try_multi_node(&parts[..3]).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_batch() {
+        let elements = [Fr::one(), Fr::zero(), -Fr::one()];
+        let bytes: Vec<u8> = elements
+            .iter()
+            .flat_map(|el| el.to_repr().to_vec())
+            .collect();
+
+        let domains = PoseidonDomain::from_bytes_batch(&bytes).expect("from_bytes_batch failure");
+        let expected: Vec<PoseidonDomain> = elements.iter().copied().map(Into::into).collect();
+        assert_eq!(domains, expected);
+
+        assert!(PoseidonDomain::from_bytes_batch(&[])
+            .expect("empty batch")
+            .is_empty());
+        assert!(PoseidonDomain::from_bytes_batch(&bytes[..40]).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_batch_non_canonical() {
+        let mut bytes = vec![0u8; 32 * 4];
+        // All bits set is larger than the field modulus.
+        bytes[64..96].copy_from_slice(&[0xff; 32]);
+
+        let err = PoseidonDomain::from_bytes_batch(&bytes).expect_err("non-canonical accepted");
+        assert!(err.to_string().contains("element 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_hash_bits() {
+        // The low bit of the first element is set, any further elements are zero.
+        let mut bits = vec![false; 3 * Fr::CAPACITY as usize];
+        bits[0] = true;
+        for len in &[1, 8, Fr::CAPACITY as usize] {
+            assert_eq!(
+                PoseidonFunction::hash_bits(&bits[..*len]),
+                shared_hash_frs(&[Fr::one(), Fr::zero()]).into()
+            );
+        }
+        assert_eq!(
+            PoseidonFunction::hash_bits(&bits),
+            shared_hash_frs(&[Fr::one(), Fr::zero(), Fr::zero(), Fr::zero()]).into()
+        );
+
+        assert_ne!(
+            PoseidonFunction::hash_bits(&[true]),
+            PoseidonFunction::hash_bits(&[false, true])
+        );
+        PoseidonFunction::hash_bits(&[]);
+        PoseidonFunction::hash_bits(&vec![true; 17 * Fr::CAPACITY as usize]);
+    }
+
+    #[test]
+    fn test_hash_into() {
+        let preimage = [0u8; 64];
+        let mut out = [0u8; 32];
+        PoseidonFunction::hash_into(&preimage[..], &mut out).expect("hash_into failed");
+        assert_eq!(
+            out.to_vec(),
+            PoseidonFunction::hash(&preimage[..]).into_bytes()
+        );
+
+        let mut short = [0u8; 31];
+        assert!(PoseidonFunction::hash_into(&preimage[..], &mut short).is_err());
+        let mut long = [0u8; 33];
+        assert!(PoseidonFunction::hash_into(&preimage[..], &mut long).is_err());
+    }
+
+    #[test]
+    fn test_try_hash_md() {
+        let input: Vec<PoseidonDomain> = (0..3u64).map(|n| Fr::from(n).into()).collect();
+        assert_eq!(
+            PoseidonFunction::try_hash_md(&input).expect("try_hash_md failed"),
+            PoseidonFunction::hash_md(&input)
+        );
+
+        for len in 0..2 {
+            let err =
+                PoseidonFunction::try_hash_md(&input[..len]).expect_err("too short input accepted");
+            assert!(err.to_string().contains("more than one element"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_hash_layer() {
+        let pairs: Vec<(PoseidonDomain, PoseidonDomain)> = (0..33u64)
+            .map(|i| (Fr::from(2 * i).into(), (-Fr::from(i)).into()))
+            .collect();
+
+        let batched = PoseidonFunction::hash_layer(&pairs);
+        assert_eq!(batched.len(), pairs.len());
+        for ((left, right), node) in pairs.iter().zip(&batched) {
+            let mut h = PoseidonFunction::default();
+            assert_eq!(*node, h.node(*left, *right, 0));
+            assert_eq!(*node, PoseidonFunction::hash2(left, right));
+        }
+
+        assert!(PoseidonFunction::hash_layer(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_as_bytes() {
+        let domain: PoseidonDomain = Fr::from(0x0102_0304u64).into();
+        assert_eq!(domain.as_bytes(), &domain.into_bytes()[..]);
+        // The bytes are borrowed from the domain element itself.
+        assert_eq!(domain.as_bytes().as_ptr(), domain.0.as_ptr());
+    }
 }
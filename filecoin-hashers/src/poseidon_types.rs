@@ -1,4 +1,6 @@
 use std::fmt::Debug;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use blstrs::Scalar as Fr;
 use generic_array::typenum::{U0, U11, U16, U2, U24, U36, U4, U8};
@@ -16,16 +18,42 @@ pub type PoseidonMDArity = U36;
 /// Must match PoseidonArity
 pub const MERKLE_TREE_ARITY: usize = 2;
 
+/// Number of sets of constants built so far, so tests can tell whether the statics below were
+/// initialized.
+#[cfg(test)]
+static CONSTANTS_BUILT: AtomicUsize = AtomicUsize::new(0);
+
+fn new_constants<A: Arity<Fr>>() -> PoseidonConstants<Fr, A> {
+    #[cfg(test)]
+    CONSTANTS_BUILT.fetch_add(1, Ordering::SeqCst);
+    PoseidonConstants::new()
+}
+
 lazy_static! {
-    pub static ref POSEIDON_CONSTANTS_2: PoseidonConstants::<Fr, U2> = PoseidonConstants::new();
-    pub static ref POSEIDON_CONSTANTS_4: PoseidonConstants::<Fr, U4> = PoseidonConstants::new();
-    pub static ref POSEIDON_CONSTANTS_8: PoseidonConstants::<Fr, U8> = PoseidonConstants::new();
-    pub static ref POSEIDON_CONSTANTS_16: PoseidonConstants::<Fr, U16> = PoseidonConstants::new();
-    pub static ref POSEIDON_CONSTANTS_24: PoseidonConstants::<Fr, U24> = PoseidonConstants::new();
-    pub static ref POSEIDON_CONSTANTS_36: PoseidonConstants::<Fr, U36> = PoseidonConstants::new();
-    pub static ref POSEIDON_CONSTANTS_11: PoseidonConstants::<Fr, U11> = PoseidonConstants::new();
+    pub static ref POSEIDON_CONSTANTS_2: PoseidonConstants::<Fr, U2> = new_constants();
+    pub static ref POSEIDON_CONSTANTS_4: PoseidonConstants::<Fr, U4> = new_constants();
+    pub static ref POSEIDON_CONSTANTS_8: PoseidonConstants::<Fr, U8> = new_constants();
+    pub static ref POSEIDON_CONSTANTS_16: PoseidonConstants::<Fr, U16> = new_constants();
+    pub static ref POSEIDON_CONSTANTS_24: PoseidonConstants::<Fr, U24> = new_constants();
+    pub static ref POSEIDON_CONSTANTS_36: PoseidonConstants::<Fr, U36> = new_constants();
+    pub static ref POSEIDON_CONSTANTS_11: PoseidonConstants::<Fr, U11> = new_constants();
     pub static ref POSEIDON_MD_CONSTANTS: PoseidonConstants::<Fr, PoseidonMDArity> =
-        PoseidonConstants::new();
+        new_constants();
+}
+
+/// Builds all of the Poseidon constants above up front. They are otherwise generated lazily on
+/// first use, which takes long enough to be noticed by the first proof. Long running processes,
+/// e.g. a storage miner daemon, should call this (re-exported by `filecoin-proofs`) once during
+/// initialization, before the first seal or PoSt.
+pub fn warm_up_poseidon_constants() {
+    lazy_static::initialize(&POSEIDON_CONSTANTS_2);
+    lazy_static::initialize(&POSEIDON_CONSTANTS_4);
+    lazy_static::initialize(&POSEIDON_CONSTANTS_8);
+    lazy_static::initialize(&POSEIDON_CONSTANTS_16);
+    lazy_static::initialize(&POSEIDON_CONSTANTS_24);
+    lazy_static::initialize(&POSEIDON_CONSTANTS_36);
+    lazy_static::initialize(&POSEIDON_CONSTANTS_11);
+    lazy_static::initialize(&POSEIDON_MD_CONSTANTS);
 }
 
 pub trait PoseidonArity: Arity<Fr> + Send + Sync + Clone + Debug {
@@ -78,3 +106,25 @@ impl PoseidonArity for U36 {
         &*POSEIDON_CONSTANTS_36
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warm_up_poseidon_constants() {
+        warm_up_poseidon_constants();
+        assert_eq!(CONSTANTS_BUILT.load(Ordering::SeqCst), 8);
+
+        // Every set of constants was built by the warm up, so none is built on use.
+        assert_eq!(U2::PARAMETERS().arity(), 2);
+        assert_eq!(U4::PARAMETERS().arity(), 4);
+        assert_eq!(U8::PARAMETERS().arity(), 8);
+        assert_eq!(U11::PARAMETERS().arity(), 11);
+        assert_eq!(U16::PARAMETERS().arity(), 16);
+        assert_eq!(U24::PARAMETERS().arity(), 24);
+        assert_eq!(U36::PARAMETERS().arity(), 36);
+        assert_eq!(POSEIDON_MD_CONSTANTS.arity(), 36);
+        assert_eq!(CONSTANTS_BUILT.load(Ordering::SeqCst), 8);
+    }
+}
@@ -1,5 +1,6 @@
+use std::convert::TryFrom;
 use std::fmt::{self, Debug, Formatter};
-use std::hash::Hasher as StdHasher;
+use std::hash::{BuildHasher, Hasher as StdHasher};
 use std::panic::panic_any;
 
 use anyhow::ensure;
@@ -17,7 +18,7 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::types::{Domain, HashFunction, Hasher};
+use crate::types::{Domain, HashFunction, Hasher, TryFromBytesError};
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Sha256Hasher {}
@@ -42,7 +43,22 @@ impl StdHasher for Sha256Function {
 
     #[inline]
     fn finish(&self) -> u64 {
-        unreachable!("unused by Function -- should never be called")
+        let digest = self.0.clone().finalize();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+/// A `BuildHasher` producing `Sha256Function`s, for keying maps by SHA-256 of their contents.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct Sha256BuildHasher;
+
+impl BuildHasher for Sha256BuildHasher {
+    type Hasher = Sha256Function;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        Sha256Function::default()
     }
 }
 
@@ -62,9 +78,18 @@ impl AsRef<Sha256Domain> for Sha256Domain {
 }
 
 impl Sha256Domain {
+    /// Returns the little-endian bytes truncated to their low `Fr::CAPACITY` (254) bits, i.e. with
+    /// the top two bits cleared. These are exactly the digest bits `hash_circuit` packs into its
+    /// output, so hashing out of circuit must go through this to agree with the circuit.
+    pub fn truncated_repr(&self) -> [u8; 32] {
+        let mut repr = self.0;
+        repr[31] &= 0xff >> (256 - Fr::CAPACITY);
+        repr
+    }
+
     fn trim_to_fr32(&mut self) {
         // strip last two bits, to ensure result is in Fr.
-        self.0[31] &= 0b0011_1111;
+        self.0 = self.truncated_repr();
     }
 }
 
@@ -92,6 +117,14 @@ impl From<Sha256Domain> for Fr {
     }
 }
 
+impl TryFrom<&[u8]> for Sha256Domain {
+    type Error = TryFromBytesError;
+
+    fn try_from(raw: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(raw).map_err(|_| TryFromBytesError { len: raw.len() })
+    }
+}
+
 impl Domain for Sha256Domain {
     fn into_bytes(&self) -> Vec<u8> {
         self.0.to_vec()
@@ -150,6 +183,21 @@ impl HashFunction<Sha256Domain> for Sha256Function {
         res
     }
 
+    /// The sha256 gadget consumes bits most significant first within each byte, so pack them
+    /// that way to agree with `hash_circuit`.
+    fn hash_bits(bits: &[bool]) -> Sha256Domain {
+        let bytes: Vec<u8> = bits
+            .chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |byte, (i, bit)| byte | ((*bit as u8) << (7 - i)))
+            })
+            .collect();
+        Self::hash(&bytes)
+    }
+
     fn hash2(a: &Sha256Domain, b: &Sha256Domain) -> Sha256Domain {
         let hashed = Sha256::new()
             .chain(AsRef::<[u8]>::as_ref(a))
@@ -320,3 +368,254 @@ impl From<Sha256Domain> for [u8; 32] {
         val.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use bellperson::{gadgets::boolean::AllocatedBit, util_cs::test_cs::TestConstraintSystem};
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    const TEST_SEED: [u8; 16] = [
+        0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
+        0xe5,
+    ];
+
+    #[test]
+    fn test_truncated_repr() {
+        let mut top_bits = [0u8; 32];
+        top_bits[31] = 0b1100_0000;
+        let mut below_top_bits = [0u8; 32];
+        below_top_bits[31] = 0b0011_1111;
+
+        for bytes in &[[0xff; 32], top_bits, below_top_bits, [0u8; 32]] {
+            let truncated = Sha256Domain(*bytes).truncated_repr();
+
+            // `hash_circuit` keeps the first `Fr::CAPACITY` little-endian bits of the digest.
+            let expected: Vec<u8> = bytes
+                .iter()
+                .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1))
+                .take(Fr::CAPACITY as usize)
+                .chain(std::iter::repeat(0))
+                .take(256)
+                .collect::<Vec<u8>>()
+                .chunks(8)
+                .map(|bits| bits.iter().rev().fold(0, |byte, bit| (byte << 1) | bit))
+                .collect();
+            assert_eq!(&truncated[..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_hash2_bytes() {
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let a = Sha256Domain::random(&mut rng);
+        let b = Sha256Domain::random(&mut rng);
+
+        let hashed =
+            Sha256Function::hash2_bytes(AsRef::<[u8]>::as_ref(&a), AsRef::<[u8]>::as_ref(&b))
+                .expect("hash2_bytes failure");
+        assert_eq!(hashed, Sha256Function::hash2(&a, &b));
+
+        assert!(Sha256Function::hash2_bytes(AsRef::<[u8]>::as_ref(&a), &[0u8; 33]).is_err());
+    }
+
+    #[test]
+    fn test_hash_layer() {
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let pairs: Vec<(Sha256Domain, Sha256Domain)> = (0..9)
+            .map(|_| {
+                (
+                    Sha256Domain::random(&mut rng),
+                    Sha256Domain::random(&mut rng),
+                )
+            })
+            .collect();
+
+        let hashed = Sha256Function::hash_layer(&pairs);
+        assert_eq!(hashed.len(), pairs.len());
+        for ((a, b), h) in pairs.iter().zip(&hashed) {
+            assert_eq!(*h, Sha256Function::hash2(a, b));
+        }
+        assert!(Sha256Function::hash_layer(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_hash_bits_matches_circuit() {
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+
+        for len in &[0, 8, 256, 512, 1024] {
+            let bits: Vec<bool> = (0..*len).map(|_| rng.gen()).collect();
+
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let bits_circuit = bits
+                .iter()
+                .enumerate()
+                .map(|(i, bit)| {
+                    AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(*bit))
+                        .map(Boolean::from)
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .expect("alloc failure");
+            let hashed_circuit =
+                Sha256Function::hash_circuit(cs.namespace(|| "hash"), &bits_circuit)
+                    .expect("hash_circuit failure");
+
+            assert!(cs.is_satisfied());
+            assert_eq!(
+                Fr::from(Sha256Function::hash_bits(&bits)),
+                hashed_circuit.get_value().expect("get_value failure")
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_hasher() {
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let domains: Vec<Sha256Domain> = (0..8).map(|_| Sha256Domain::random(&mut rng)).collect();
+
+        let mut map = HashMap::with_hasher(Sha256BuildHasher);
+        for (i, domain) in domains.iter().enumerate() {
+            map.insert(*domain, i);
+        }
+
+        assert_eq!(map.len(), domains.len());
+        for (i, domain) in domains.iter().enumerate() {
+            assert_eq!(map.get(domain), Some(&i));
+        }
+        assert_eq!(map.get(&Sha256Domain::random(&mut rng)), None);
+    }
+
+    #[test]
+    fn test_try_from_slice() {
+        let domain = Sha256Domain::random(&mut XorShiftRng::from_seed(TEST_SEED));
+        let bytes = domain.into_bytes();
+
+        let parsed = Sha256Domain::try_from(bytes.as_slice()).expect("try_from failure");
+        assert_eq!(parsed, domain);
+
+        for len in &[0, 31, 33] {
+            let err = Sha256Domain::try_from(vec![0u8; *len].as_slice())
+                .expect_err("invalid length accepted");
+            assert_eq!(err, TryFromBytesError { len: *len });
+        }
+    }
+
+    #[test]
+    fn test_hash_empty() {
+        let empty = Sha256Function::hash_empty();
+        assert_eq!(empty, Sha256Function::hash_empty());
+        assert_eq!(empty, Sha256Function::hash(&[]));
+
+        #[cfg(feature = "poseidon")]
+        assert_ne!(
+            AsRef::<[u8]>::as_ref(&empty),
+            AsRef::<[u8]>::as_ref(&crate::poseidon::PoseidonFunction::hash_empty())
+        );
+    }
+
+    #[test]
+    fn test_hash_known_answers() {
+        // FIPS 180-2 test vectors, with the two most significant bits of the last byte cleared to
+        // fit the field. These hold for both the portable and the `asm` backend.
+        let vectors: [(&[u8], &str); 3] = [
+            (
+                b"",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b815",
+            ),
+            (
+                b"abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f200152d",
+            ),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db0601",
+            ),
+        ];
+
+        for (input, expected) in &vectors {
+            let hashed = Sha256Function::hash(input);
+            assert_eq!(hex::encode(AsRef::<[u8]>::as_ref(&hashed)), *expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_into() {
+        let preimage = b"hash_into";
+        let mut out = [0u8; 32];
+        Sha256Function::hash_into(&preimage[..], &mut out).expect("hash_into failed");
+        assert_eq!(
+            out.to_vec(),
+            Sha256Function::hash(&preimage[..]).into_bytes()
+        );
+
+        let mut short = [0u8; 31];
+        assert!(Sha256Function::hash_into(&preimage[..], &mut short).is_err());
+        let mut long = [0u8; 33];
+        assert!(Sha256Function::hash_into(&preimage[..], &mut long).is_err());
+    }
+
+    #[test]
+    fn test_hash_with() {
+        let d = Sha256Function::hash(b"hash_with");
+        assert_eq!(
+            d.hash_with::<Sha256Function>(),
+            Sha256Function::hash(AsRef::<[u8]>::as_ref(&d))
+        );
+        assert_ne!(d.hash_with::<Sha256Function>(), d);
+    }
+
+    #[cfg(feature = "poseidon")]
+    #[test]
+    fn test_cross_hash() {
+        use crate::poseidon::{PoseidonDomain, PoseidonFunction};
+
+        // SHA-256 outputs are trimmed to 254 bits, but only those below the modulus convert.
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let leaf = Sha256Domain::random(&mut rng);
+        let node = leaf
+            .cross_hash::<PoseidonFunction, PoseidonDomain>()
+            .expect("cross_hash failed");
+        assert_eq!(AsRef::<[u8]>::as_ref(&node), AsRef::<[u8]>::as_ref(&leaf));
+        assert_eq!(Fr::from(node), Fr::from(leaf));
+
+        // The result can be hashed into a Poseidon tree like any other leaf.
+        let parent = PoseidonFunction::hash2(&node, &node);
+        assert_ne!(parent, node);
+
+        assert!(Sha256Domain([0xff; 32])
+            .cross_hash::<PoseidonFunction, PoseidonDomain>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_try_from_bytes_in_field() {
+        let modulus = Sha256Domain::field_modulus_bytes();
+        assert!(Fr::from_repr_vartime(modulus).is_none());
+        assert!(Sha256Domain::try_from_bytes_in_field(&modulus).is_err());
+        // Plain `try_from_bytes` does not check the range.
+        assert!(Sha256Domain::try_from_bytes(&modulus).is_ok());
+
+        let largest = Sha256Domain::from(-Fr::one());
+        assert_eq!(
+            Sha256Domain::try_from_bytes_in_field(&largest.0).expect("in range"),
+            largest
+        );
+        assert!(Sha256Domain::try_from_bytes_in_field(&[0xff; 32]).is_err());
+        assert!(Sha256Domain::try_from_bytes_in_field(&[0; 31]).is_err());
+
+        #[cfg(feature = "poseidon")]
+        assert_eq!(
+            crate::poseidon::PoseidonDomain::field_modulus_bytes(),
+            modulus
+        );
+        #[cfg(feature = "blake2s")]
+        assert_eq!(
+            crate::blake2s::Blake2sDomain::field_modulus_bytes(),
+            modulus
+        );
+    }
+}
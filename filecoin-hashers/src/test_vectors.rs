@@ -0,0 +1,136 @@
+//! Fixed digests for each hasher, so that a change in the underlying hash implementations (or in
+//! the way their output is mapped into the field) shows up as a test failure rather than as
+//! silently incompatible commitments.
+//!
+//! Preimages and digests are given as hex; field elements are in their little-endian canonical
+//! encoding, which is also how the domain types store them.
+
+use crate::{Domain, HashFunction};
+
+/// The little-endian encoding of the field element `1`, followed by that of `2`.
+#[allow(dead_code)]
+const ONE_TWO: &str = "0100000000000000000000000000000000000000000000000000000000000000\
+                       0200000000000000000000000000000000000000000000000000000000000000";
+
+#[allow(dead_code)]
+fn check_hash<D: Domain, F: HashFunction<D>>(vectors: &[(&str, &str)]) {
+    for (preimage, expected) in vectors {
+        let preimage = hex::decode(preimage).expect("invalid preimage hex");
+        let digest = F::hash(&preimage);
+        assert_eq!(
+            hex::encode(AsRef::<[u8]>::as_ref(&digest)),
+            *expected,
+            "hash of {}",
+            hex::encode(&preimage)
+        );
+    }
+}
+
+#[allow(dead_code)]
+fn check_hash2<D: Domain, F: HashFunction<D>>(vectors: &[(&str, &str)]) {
+    for (preimage, expected) in vectors {
+        let preimage = hex::decode(preimage).expect("invalid preimage hex");
+        assert_eq!(
+            preimage.len(),
+            64,
+            "hash2 preimages are two domain elements"
+        );
+        let a = D::try_from_bytes(&preimage[..32]).expect("invalid left element");
+        let b = D::try_from_bytes(&preimage[32..]).expect("invalid right element");
+        let digest = F::hash2(&a, &b);
+        assert_eq!(
+            hex::encode(AsRef::<[u8]>::as_ref(&digest)),
+            *expected,
+            "hash2 of {}",
+            hex::encode(&preimage)
+        );
+    }
+}
+
+#[cfg(feature = "poseidon")]
+#[test]
+fn test_vectors_poseidon() {
+    use crate::poseidon::{PoseidonDomain, PoseidonFunction};
+
+    // Only arity 2 is covered: `hash` of 64 bytes and `hash2` share the same constants.
+    check_hash::<PoseidonDomain, PoseidonFunction>(&[(
+        ONE_TWO,
+        "bea95f3e83d91793d896586e724ec069769d6a43afcbab7b4d1f7f6506816f6d",
+    )]);
+    check_hash2::<PoseidonDomain, PoseidonFunction>(&[
+        (
+            ONE_TWO,
+            "bea95f3e83d91793d896586e724ec069769d6a43afcbab7b4d1f7f6506816f6d",
+        ),
+        (
+            "0000000000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000",
+            "d413460e7f14e58b413d1defe188d36fb761afe5c6a733db6c6f1931130bfe48",
+        ),
+        (
+            // The largest field element, and 12345.
+            "00000000fffffffffe5bfeff02a4bd5305d8a10908d83933487d9d2953a7ed73\
+             3930000000000000000000000000000000000000000000000000000000000000",
+            "34e68f11dcd0be49c2eba1cfcceb81f89b2e5ea46bbbc0a0556fe8e762538014",
+        ),
+    ]);
+}
+
+#[cfg(feature = "sha256")]
+#[test]
+fn test_vectors_sha256() {
+    use crate::sha256::{Sha256Domain, Sha256Function};
+
+    check_hash::<Sha256Domain, Sha256Function>(&[
+        (
+            "",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b815",
+        ),
+        (
+            "616263",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f200152d",
+        ),
+    ]);
+    check_hash2::<Sha256Domain, Sha256Function>(&[(
+        ONE_TWO,
+        "ff55c97976a840b4ced964ed49e3794594ba3f675238b5fd25d282b60f70a114",
+    )]);
+}
+
+#[cfg(feature = "blake2s")]
+#[test]
+fn test_vectors_blake2s() {
+    use crate::blake2s::{Blake2sDomain, Blake2sFunction};
+
+    check_hash::<Blake2sDomain, Blake2sFunction>(&[
+        (
+            "",
+            "69217a3079908094e11121d042354a7c1f55b6482ca1a51e1b250dfd1ed0ee39",
+        ),
+        (
+            "616263",
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675902",
+        ),
+    ]);
+    check_hash2::<Blake2sDomain, Blake2sFunction>(&[(
+        ONE_TWO,
+        "1466784a2149964c3bb5af60fb274365a73ced9e96459ea486fe330a3afa4137",
+    )]);
+
+    // Blake2s uses the default `hash_bits`, which packs bits least significant first.
+    let abc_bits: Vec<bool> = b"abc"
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .collect();
+    let abc = Blake2sFunction::hash_bits(&abc_bits);
+    assert_eq!(
+        hex::encode(AsRef::<[u8]>::as_ref(&abc)),
+        "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675902"
+    );
+    // A trailing partial byte is padded with zero bits.
+    let one = Blake2sFunction::hash_bits(&[true]);
+    assert_eq!(
+        hex::encode(AsRef::<[u8]>::as_ref(&one)),
+        "fc87f4f1d721942d282aa61989c069b290d1447d20d4b811430f47ea0edf9911"
+    );
+}
@@ -1,4 +1,5 @@
-use std::fmt::Debug;
+use std::error::Error as StdError;
+use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash as StdHash;
 
 #[cfg(feature = "poseidon")]
@@ -9,7 +10,7 @@ use bellperson::{
     ConstraintSystem, SynthesisError,
 };
 use blstrs::Scalar as Fr;
-use ff::PrimeField;
+use ff::{Field, PrimeField};
 use merkletree::{
     hash::{Algorithm as LightAlgorithm, Hashable as LightHashable},
     merkle::Element,
@@ -42,11 +43,150 @@ pub trait Domain:
     fn write_bytes(&self, _: &mut [u8]) -> anyhow::Result<()>;
 
     fn random<R: RngCore>(rng: &mut R) -> Self;
+
+    /// Returns the modulus of the field this domain's elements belong to, LittleEndian bytes.
+    /// Only byte strings below it are canonical elements, see `try_from_bytes_in_field`.
+    fn field_modulus_bytes() -> [u8; 32] {
+        // The modulus is one more than the largest element, `-1`.
+        let mut modulus: [u8; 32] = (-Fr::one()).to_repr();
+        for byte in modulus.iter_mut() {
+            let (sum, carry) = byte.overflowing_add(1);
+            *byte = sum;
+            if !carry {
+                break;
+            }
+        }
+        modulus
+    }
+
+    /// Like `try_from_bytes`, but also rejects bytes which are not below `field_modulus_bytes`,
+    /// i.e. not a canonical field element, e.g. to validate bytes from a system over another
+    /// field before using them as an element of this one.
+    fn try_from_bytes_in_field(raw: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            raw.len() == 32,
+            "cannot convert {} bytes into a field element",
+            raw.len()
+        );
+        let modulus = Self::field_modulus_bytes();
+        anyhow::ensure!(
+            raw.iter().rev().lt(modulus.iter().rev()),
+            "bytes are not below the field modulus"
+        );
+        Self::try_from_bytes(raw)
+    }
+
+    /// Hashes the bytes of this element with `HF`, e.g. to derive a commitment from a root.
+    ///
+    /// Note that Poseidon has no arity for a single element, so this panics for
+    /// `PoseidonFunction`; hash two elements with `hash2` instead.
+    fn hash_with<HF: HashFunction<Self>>(&self) -> Self {
+        HF::hash(AsRef::<[u8]>::as_ref(self))
+    }
+
+    /// Converts this element into a leaf of a tree over `Out` built with `HF`, e.g. to build the
+    /// upper layers of a tree with a different hasher than its lower layers. The bytes are
+    /// reinterpreted rather than rehashed, so they must be a canonical field element of the
+    /// length `Out` expects; otherwise an error is returned.
+    fn cross_hash<HF: HashFunction<Out>, Out: Domain>(&self) -> anyhow::Result<Out> {
+        let bytes = AsRef::<[u8]>::as_ref(self);
+        let mut repr = <Fr as PrimeField>::Repr::default();
+        anyhow::ensure!(
+            bytes.len() == Out::byte_len() && bytes.len() == repr.as_ref().len(),
+            "cannot convert {} bytes into a domain element of {} bytes",
+            bytes.len(),
+            Out::byte_len()
+        );
+        repr.as_mut().copy_from_slice(bytes);
+        let fr = Fr::from_repr_vartime(repr)
+            .ok_or_else(|| anyhow::anyhow!("domain element is not a canonical field element"))?;
+
+        Ok(HF::default().leaf(Out::from(fr)))
+    }
+}
+
+/// Error returned by the `TryFrom<&[u8]>` implementations of the concrete domains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryFromBytesError {
+    /// Length of the rejected byte slice.
+    pub len: usize,
 }
 
+impl Display for TryFromBytesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot convert {} bytes into a domain element", self.len)
+    }
+}
+
+impl StdError for TryFromBytesError {}
+
 pub trait HashFunction<T: Domain>: Clone + Debug + Send + Sync + LightAlgorithm<T> {
     fn hash(data: &[u8]) -> T;
     fn hash2(a: &T, b: &T) -> T;
+
+    /// Hashes `preimage` and writes the resulting domain element into `out`, which must have the
+    /// length of a domain element.
+    fn hash_into(preimage: &[u8], out: &mut [u8]) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            out.len() == T::byte_len(),
+            "cannot write a domain element of {} bytes into {} bytes",
+            T::byte_len(),
+            out.len()
+        );
+        Self::hash(preimage).write_bytes(out)
+    }
+
+    /// Hashes two raw nodes, first converting each into a domain element. Fails if either slice
+    /// does not have the length of a domain element.
+    fn hash2_bytes(a: &[u8], b: &[u8]) -> anyhow::Result<T> {
+        let a = T::try_from_bytes(a)?;
+        let b = T::try_from_bytes(b)?;
+        Ok(Self::hash2(&a, &b))
+    }
+
+    /// Hashes a bit string. By default each group of eight bits is packed into a byte least
+    /// significant bit first, a trailing partial byte is padded with zero bits, and the bytes are
+    /// hashed with `hash`. Implementations which pack differently document their order.
+    fn hash_bits(bits: &[bool]) -> T {
+        let bytes: Vec<u8> = bits
+            .chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |byte, (i, bit)| byte | ((*bit as u8) << i))
+            })
+            .collect();
+        Self::hash(&bytes)
+    }
+
+    /// Like `hash_md`, but returns an error instead of panicking when `input` has fewer than two
+    /// elements.
+    fn try_hash_md(input: &[T]) -> anyhow::Result<T> {
+        anyhow::ensure!(
+            input.len() > 1,
+            "hash_md needs more than one element, got {}",
+            input.len()
+        );
+        Ok(Self::hash_md(input))
+    }
+
+    /// Hashes each pair of a binary tree layer into its parent node, equal to calling `node` (and
+    /// so `hash2`) on every pair. Use it to hash any batch of independent pairs; implementations
+    /// may override it to amortize per-hash setup over the batch.
+    fn hash_layer(pairs: &[(T, T)]) -> Vec<T> {
+        pairs
+            .iter()
+            .map(|(left, right)| Self::default().node(*left, *right, 0))
+            .collect()
+    }
+
+    /// Returns the hash of the empty input, e.g. to pad sparse Merkle trees. Implementations for
+    /// which this is expensive cache the result.
+    fn hash_empty() -> T {
+        Self::hash(&[])
+    }
+
     fn hash_md(input: &[T]) -> T {
         // Default to binary.
         assert!(input.len() > 1, "hash_md needs more than one element.");
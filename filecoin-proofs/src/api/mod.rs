@@ -55,6 +55,7 @@ pub use util::*;
 pub use window_post::*;
 pub use winning_post::*;
 
+pub use filecoin_hashers::warm_up_poseidon_constants;
 pub use storage_proofs_update::constants::{hs, partition_count};
 
 /// Unseals the sector at `sealed_path` and returns the bytes for a piece
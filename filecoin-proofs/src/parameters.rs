@@ -134,7 +134,10 @@ fn select_challenges(
 mod tests {
     use super::*;
 
-    use crate::{DefaultOctLCTree, PoRepProofPartitions, PoStType};
+    use storage_proofs_core::parameter_cache::{get_parameter_data, CacheableParameters};
+    use storage_proofs_post::fallback::{FallbackPoStCircuit, FallbackPoStCompound};
+
+    use crate::{DefaultOctLCTree, PoRepProofPartitions, PoStType, SectorShape2KiB};
 
     #[test]
     fn partition_layer_challenges_test() {
@@ -164,4 +167,31 @@ mod tests {
         assert_eq!(params.challenge_count, 1);
         assert_eq!(params.sector_size, 2048);
     }
+
+    #[test]
+    fn test_winning_post_cache_identifier_is_published() {
+        let config = PoStConfig {
+            typ: PoStType::Winning,
+            priority: false,
+            challenge_count: 66,
+            sector_count: 1,
+            sector_size: 2048u64.into(),
+            api_version: ApiVersion::V1_0_0,
+        };
+
+        let params =
+            winning_post_public_params::<SectorShape2KiB>(&config).expect("failed to get params");
+        let cache_id = <FallbackPoStCompound<SectorShape2KiB> as CacheableParameters<
+            FallbackPoStCircuit<SectorShape2KiB>,
+            _,
+        >>::cache_identifier(&params);
+
+        // Changing the identifier of a parameter set orphans the published parameters.
+        assert_eq!(
+            cache_id,
+            "proof-of-spacetime-fallback-merkletree-poseidon_hasher-8-0-0-\
+             3ea05428c9d11689f23529cde32fd30aabd50f7d2c93657c1d3650bca3e8ea9e"
+        );
+        assert!(get_parameter_data(&cache_id).is_some());
+    }
 }
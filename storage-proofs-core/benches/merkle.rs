@@ -1,11 +1,36 @@
-use anyhow::Result;
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{
+    black_box, criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, Criterion,
+};
 use filecoin_hashers::{
-    poseidon::PoseidonDomain, poseidon::PoseidonHasher, sha256::Sha256Hasher, Domain,
+    poseidon::{PoseidonDomain, PoseidonFunction, PoseidonHasher},
+    sha256::Sha256Hasher,
+    Domain, HashFunction, Hasher,
 };
-use rand::{thread_rng, Rng};
+use merkletree::hash::Algorithm;
+use rand::thread_rng;
 use storage_proofs_core::merkle::{create_base_merkle_tree, BinaryMerkleTree};
 
+/// Benchmarks building a binary merkle tree over `n_nodes` random leaves using the hasher `H`.
+fn bench_tree<H: 'static + Hasher>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    label: &str,
+    n_nodes: usize,
+) {
+    group.bench_function(format!("{}-{}", label, n_nodes), |b| {
+        let mut rng = thread_rng();
+        let data: Vec<u8> = (0..n_nodes)
+            .flat_map(|_| H::Domain::random(&mut rng).into_bytes())
+            .collect();
+
+        b.iter(|| {
+            black_box(
+                create_base_merkle_tree::<BinaryMerkleTree<H>>(None, n_nodes, &data)
+                    .expect("failed to create merkle tree"),
+            )
+        })
+    });
+}
+
 fn merkle_benchmark_sha256(c: &mut Criterion) {
     let params = if cfg!(feature = "big-sector-sizes-bench") {
         vec![128, 1024, 1_048_576]
@@ -15,16 +40,7 @@ fn merkle_benchmark_sha256(c: &mut Criterion) {
 
     let mut group = c.benchmark_group("merkletree-binary");
     for n_nodes in params {
-        group.bench_function(format!("sha256-{}", n_nodes), |b| {
-            let mut rng = thread_rng();
-            let data: Vec<u8> = (0..32 * n_nodes).map(|_| rng.gen()).collect();
-            b.iter(|| {
-                black_box(
-                    create_base_merkle_tree::<BinaryMerkleTree<Sha256Hasher>>(None, n_nodes, &data)
-                        .unwrap(),
-                )
-            })
-        });
+        bench_tree::<Sha256Hasher>(&mut group, "sha256", n_nodes);
     }
 
     group.finish();
@@ -39,24 +55,37 @@ fn merkle_benchmark_poseidon(c: &mut Criterion) {
 
     let mut group = c.benchmark_group("merkletree-binary");
     for n_nodes in params {
-        group.bench_function(format!("poseidon-{}", n_nodes), |b| {
-            let mut rng = thread_rng();
-            let mut data: Vec<u8> = Vec::with_capacity(32 * n_nodes);
-            (0..n_nodes)
-                .into_iter()
-                .try_for_each(|_| -> Result<()> {
-                    let node = PoseidonDomain::random(&mut rng);
-                    data.extend(node.into_bytes());
-                    Ok(())
-                })
-                .expect("failed to generate data");
+        bench_tree::<PoseidonHasher>(&mut group, "poseidon", n_nodes);
+    }
+
+    group.finish();
+}
+
+fn hash_layer_benchmark_poseidon(c: &mut Criterion) {
+    let params = vec![64, 1024, 16_384];
 
+    let mut group = c.benchmark_group("poseidon-hash-layer");
+    for n_pairs in params {
+        let mut rng = thread_rng();
+        let pairs: Vec<(PoseidonDomain, PoseidonDomain)> = (0..n_pairs)
+            .map(|_| {
+                (
+                    PoseidonDomain::random(&mut rng),
+                    PoseidonDomain::random(&mut rng),
+                )
+            })
+            .collect();
+
+        group.bench_function(format!("batched-{}", n_pairs), |b| {
+            b.iter(|| black_box(PoseidonFunction::hash_layer(&pairs)))
+        });
+        group.bench_function(format!("per-pair-{}", n_pairs), |b| {
             b.iter(|| {
                 black_box(
-                    create_base_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(
-                        None, n_nodes, &data,
-                    )
-                    .unwrap(),
+                    pairs
+                        .iter()
+                        .map(|(left, right)| PoseidonFunction::default().node(*left, *right, 0))
+                        .collect::<Vec<_>>(),
                 )
             })
         });
@@ -65,5 +94,10 @@ fn merkle_benchmark_poseidon(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, merkle_benchmark_sha256, merkle_benchmark_poseidon);
+criterion_group!(
+    benches,
+    merkle_benchmark_sha256,
+    merkle_benchmark_poseidon,
+    hash_layer_benchmark_poseidon
+);
 criterion_main!(benches);
@@ -9,6 +9,7 @@ use bellperson::{
 };
 use blstrs::{Bls12, Scalar as Fr};
 use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use filecoin_hashers::{sha256::Sha256Function, HashFunction};
 use rand::{thread_rng, Rng};
 use sha2::Digest;
 
@@ -55,6 +56,25 @@ fn sha256_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+/// Run with and without the `asm` feature to compare the SHA-256 backends.
+fn sha256_function_benchmark(c: &mut Criterion) {
+    let params = vec![32, 64, 10 * 32, 37 * 32];
+
+    let mut group = c.benchmark_group("hash-sha256-function");
+    for bytes in params {
+        group
+            .bench_function(format!("non-circuit-{}", bytes), |b| {
+                let mut rng = thread_rng();
+                let data: Vec<u8> = (0..bytes).map(|_| rng.gen()).collect();
+
+                b.iter(|| black_box(Sha256Function::hash(&data)))
+            })
+            .throughput(Throughput::Bytes(bytes as u64));
+    }
+
+    group.finish();
+}
+
 fn sha256_raw_benchmark(c: &mut Criterion) {
     let params = vec![64, 10 * 32, 38 * 32];
 
@@ -130,6 +150,7 @@ fn sha256_circuit_benchmark(c: &mut Criterion) {
 criterion_group!(
     benches,
     sha256_benchmark,
+    sha256_function_benchmark,
     sha256_raw_benchmark,
     sha256_circuit_benchmark
 );
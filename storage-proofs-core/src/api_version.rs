@@ -3,6 +3,7 @@ use std::str::FromStr;
 
 use anyhow::{format_err, Error, Result};
 use semver::Version;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub enum ApiVersion {
@@ -17,6 +18,12 @@ impl ApiVersion {
             ApiVersion::V1_1_0 => Version::new(1, 1, 0),
         }
     }
+
+    /// Returns true if this version is the same as or newer than `other`, comparing by
+    /// semantic version rather than by variant order.
+    pub fn at_least(&self, other: ApiVersion) -> bool {
+        self.as_semver() >= other.as_semver()
+    }
 }
 
 impl Debug for ApiVersion {
@@ -36,6 +43,8 @@ impl Display for ApiVersion {
 impl FromStr for ApiVersion {
     type Err = Error;
     fn from_str(api_version_str: &str) -> Result<Self> {
+        // Accept the common `v1.1.0` spelling used in tags and CLI flags.
+        let api_version_str = api_version_str.strip_prefix('v').unwrap_or(api_version_str);
         let api_version = Version::parse(api_version_str)?;
         match (api_version.major, api_version.minor, api_version.patch) {
             (1, 0, 0) => Ok(ApiVersion::V1_0_0),
@@ -53,6 +62,19 @@ impl FromStr for ApiVersion {
     }
 }
 
+impl Serialize for ApiVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ApiVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let api_version_str = String::deserialize(deserializer)?;
+        ApiVersion::from_str(&api_version_str).map_err(de::Error::custom)
+    }
+}
+
 #[test]
 fn test_fmt() {
     assert_eq!(format!("{}", ApiVersion::V1_0_0), "1.0.0");
@@ -64,3 +86,55 @@ fn test_as_semver() {
     assert_eq!(ApiVersion::V1_0_0.as_semver().major, 1);
     assert_eq!(ApiVersion::V1_1_0.as_semver().major, 1);
 }
+
+#[test]
+fn test_as_semver_ordering() {
+    assert!(ApiVersion::V1_0_0.as_semver() < ApiVersion::V1_1_0.as_semver());
+
+    assert!(ApiVersion::V1_1_0.at_least(ApiVersion::V1_0_0));
+    assert!(!ApiVersion::V1_0_0.at_least(ApiVersion::V1_1_0));
+}
+
+#[test]
+fn test_at_least_reflexive() {
+    for api_version in &[ApiVersion::V1_0_0, ApiVersion::V1_1_0] {
+        assert!(api_version.at_least(*api_version));
+    }
+}
+
+#[test]
+fn test_from_str_round_trip() {
+    for api_version in &[ApiVersion::V1_0_0, ApiVersion::V1_1_0] {
+        let parsed = ApiVersion::from_str(&api_version.to_string()).expect("failed to parse");
+        assert_eq!(&parsed, api_version);
+    }
+}
+
+#[test]
+fn test_from_str_prefixed() {
+    assert_eq!(
+        ApiVersion::from_str("v1.1.0").expect("failed to parse"),
+        ApiVersion::V1_1_0
+    );
+    assert_eq!(
+        ApiVersion::from_str("v1.0.0").expect("failed to parse"),
+        ApiVersion::V1_0_0
+    );
+}
+
+#[test]
+fn test_serde_round_trip() {
+    for api_version in &[ApiVersion::V1_0_0, ApiVersion::V1_1_0] {
+        let json = serde_json::to_string(api_version).expect("failed to serialize");
+        assert_eq!(json, format!("\"{}\"", api_version));
+        let parsed: ApiVersion = serde_json::from_str(&json).expect("failed to deserialize");
+        assert_eq!(&parsed, api_version);
+    }
+}
+
+#[test]
+fn test_from_str_unknown() {
+    assert!(ApiVersion::from_str("9.9.9").is_err());
+    assert!(ApiVersion::from_str("1.2.0").is_err());
+    assert!(ApiVersion::from_str("vv1.1.0").is_err());
+}
@@ -1,3 +1,4 @@
+use filecoin_hashers::{Domain, Hasher};
 use sha2::{Digest, Sha256};
 
 pub mod aes;
@@ -20,3 +21,78 @@ pub fn derive_porep_domain_seed(
         .finalize()
         .into()
 }
+
+/// Reduces `domain` to a challenge scalar in its field: the SHA-256 digest of its bytes, with
+/// every bit from the most significant bit of `Domain::field_modulus_bytes` up cleared. This
+/// leaves the result below the modulus of whichever field `H` is over, rather than truncating to
+/// a fixed number of bits like `bytes_into_fr_repr_safe`.
+pub fn domain_to_challenge_scalar<H: Hasher>(domain: &H::Domain) -> H::Domain {
+    let mut digest: [u8; 32] = Sha256::digest(AsRef::<[u8]>::as_ref(domain)).into();
+
+    let modulus = H::Domain::field_modulus_bytes();
+    let modulus_bits = modulus
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, byte)| **byte != 0)
+        .map_or(0, |(i, byte)| 8 * i + 8 - byte.leading_zeros() as usize);
+    let kept_bits = modulus_bits.saturating_sub(1);
+    for (i, byte) in digest.iter_mut().enumerate() {
+        if 8 * i >= kept_bits {
+            *byte = 0;
+        } else if 8 * (i + 1) > kept_bits {
+            *byte &= (1u8 << (kept_bits - 8 * i)) - 1;
+        }
+    }
+
+    H::Domain::try_from_bytes_in_field(&digest).expect("masked digest is below the modulus")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blstrs::Scalar as Fr;
+    use ff::PrimeField;
+    use filecoin_hashers::{poseidon::PoseidonHasher, sha256::Sha256Hasher};
+    use fr32::bytes_into_fr_repr_safe;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::TEST_SEED;
+
+    fn check_domain_to_challenge_scalar<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        for _ in 0..100 {
+            let domain = H::Domain::random(rng);
+            let scalar = domain_to_challenge_scalar::<H>(&domain);
+            assert_eq!(scalar, domain_to_challenge_scalar::<H>(&domain));
+
+            let mut repr = <Fr as PrimeField>::Repr::default();
+            repr.copy_from_slice(AsRef::<[u8]>::as_ref(&scalar));
+            assert!(Fr::from_repr_vartime(repr).is_some());
+
+            // For BLS12-381 this is the same reduction as `bytes_into_fr_repr_safe`.
+            let digest = Sha256::digest(AsRef::<[u8]>::as_ref(&domain));
+            assert_eq!(
+                AsRef::<[u8]>::as_ref(&scalar),
+                &bytes_into_fr_repr_safe(&digest)[..]
+            );
+        }
+
+        let a = domain_to_challenge_scalar::<H>(&H::Domain::random(rng));
+        let b = domain_to_challenge_scalar::<H>(&H::Domain::random(rng));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn domain_to_challenge_scalar_sha256() {
+        check_domain_to_challenge_scalar::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn domain_to_challenge_scalar_poseidon() {
+        check_domain_to_challenge_scalar::<PoseidonHasher>();
+    }
+}
@@ -1,14 +1,20 @@
 use std::cmp::{max, min};
+use std::convert::TryInto;
 use std::fmt::Debug;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
 
 use anyhow::ensure;
 use filecoin_hashers::{Hasher, PoseidonArity};
 use fr32::bytes_into_fr_repr_safe;
 use generic_array::typenum::Unsigned;
+use log::info;
 use merkletree::merkle::get_merkle_tree_row_count;
-use rand::{Rng, SeedableRng};
+use rand::{Rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use rayon::prelude::{IndexedParallelIterator, ParallelIterator, ParallelSliceMut};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use sha2::{Digest, Sha256};
 
 use crate::{
@@ -31,8 +37,14 @@ pub const BASE_DEGREE: usize = 6;
 pub trait Graph<H: Hasher>: Debug + Clone + PartialEq + Eq {
     type Key: Debug;
 
-    /// Returns the expected size of all nodes in the graph.
+    /// Returns the expected size of all nodes in the graph. Same as `data_size`.
     fn expected_size(&self) -> usize {
+        self.data_size()
+    }
+
+    /// Returns the size in bytes of the data the graph is laid over, one node per `NODE_SIZE`
+    /// bytes.
+    fn data_size(&self) -> usize {
         self.size() * NODE_SIZE
     }
 
@@ -51,12 +63,161 @@ pub trait Graph<H: Hasher>: Debug + Clone + PartialEq + Eq {
     /// reasons, so that the vector can be allocated outside this call.
     fn parents(&self, node: usize, parents: &mut [u32]) -> Result<()>;
 
+    /// Like `parents`, but resizes `out` to `degree()` entries instead of relying on the caller to
+    /// pass a buffer of the right length, and checks that the base parents respect the topological
+    /// order: every one precedes `node`, except for the self reference of nodes without parents
+    /// (see `degree_for_node`). Expansion parents refer to another layer and are not checked.
+    ///
+    /// `out` holds `u32`s rather than `usize`s, like the buffer of `parents` and the rows of
+    /// `ParentMatrix`, so it can be passed on without converting every parent. It always has
+    /// `degree()` entries, not `degree_for_node(node)`: nodes with fewer parents still fill every
+    /// entry, and keys and proofs are computed over all of them.
+    fn parents_checked(&self, node: usize, out: &mut Vec<u32>) -> Result<()> {
+        ensure!(
+            node < self.size(),
+            "node {} is out of range for a graph of {} nodes",
+            node,
+            self.size()
+        );
+
+        out.resize(self.degree(), 0);
+        self.parents(node, out)?;
+
+        let has_parents = self.degree_for_node(node) > 0;
+        for parent in &out[..self.base_degree()] {
+            let parent = *parent as usize;
+            ensure!(
+                parent < node || (!has_parents && parent == node),
+                "parent {} of node {} does not precede it",
+                parent,
+                node
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Computes the parents of every node at once, in parallel.
+    fn parents_all(&self) -> Result<ParentMatrix>
+    where
+        Self: Sync,
+    {
+        let degree = self.degree();
+        let mut parents = vec![0; self.size() * degree];
+        if degree > 0 {
+            parents
+                .par_chunks_mut(degree)
+                .enumerate()
+                .try_for_each(|(node, row)| self.parents(node, row))?;
+        }
+
+        Ok(ParentMatrix { degree, parents })
+    }
+
+    /// Partitions the nodes into layers, such that the base parents of every node are in earlier
+    /// layers, e.g. to encode all nodes of a layer in parallel. As in `parents_checked`,
+    /// expansion parents refer to the previous layer of a stacked graph, which is encoded in
+    /// full before this one, so they impose no order here and are ignored, as are the self
+    /// references of nodes without parents. Nodes within a layer are sorted; fails if the base
+    /// parents form a cycle.
+    fn layering(&self) -> Result<Vec<Vec<usize>>> {
+        let size = self.size();
+        let mut parents = vec![0; self.degree()];
+        let mut children = vec![Vec::new(); size];
+        let mut pending = vec![0usize; size];
+        for node in 0..size {
+            self.parents(node, &mut parents)?;
+            let mut distinct: Vec<usize> = parents[..self.base_degree()]
+                .iter()
+                .map(|parent| *parent as usize)
+                .filter(|parent| *parent != node)
+                .collect();
+            distinct.sort_unstable();
+            distinct.dedup();
+            for parent in distinct {
+                ensure!(
+                    parent < size,
+                    "parent {} of node {} is out of range",
+                    parent,
+                    node
+                );
+                children[parent].push(node);
+                pending[node] += 1;
+            }
+        }
+
+        let mut layers = Vec::new();
+        let mut layered = 0;
+        let mut frontier: Vec<usize> = (0..size).filter(|node| pending[*node] == 0).collect();
+        while !frontier.is_empty() {
+            layered += frontier.len();
+            let mut next = Vec::new();
+            for node in &frontier {
+                for child in &children[*node] {
+                    pending[*child] -= 1;
+                    if pending[*child] == 0 {
+                        next.push(*child);
+                    }
+                }
+            }
+            next.sort_unstable();
+            layers.push(std::mem::replace(&mut frontier, next));
+        }
+        ensure!(
+            layered == size,
+            "base parents form a cycle, {} of {} nodes cannot be layered",
+            size - layered,
+            size
+        );
+
+        Ok(layers)
+    }
+
+    /// Returns the parents of `n_samples` nodes drawn uniformly at random, for statistical
+    /// analysis of the graph's topology. Early nodes with fewer than `degree()` actual parents
+    /// (see `degree_for_node`) are never sampled; if there are no other nodes, nothing is.
+    fn sample_parents<R: RngCore>(&self, rng: &mut R, n_samples: usize) -> Result<Vec<Vec<usize>>> {
+        let degree = self.degree();
+        let first = match (0..self.size()).find(|&node| self.degree_for_node(node) == degree) {
+            Some(first) if degree > 0 => first,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut parents = vec![0; degree];
+        (0..n_samples)
+            .map(|_| {
+                let node = rng.gen_range(first..self.size());
+                self.parents(node, &mut parents)?;
+                Ok(parents.iter().map(|&parent| parent as usize).collect())
+            })
+            .collect()
+    }
+
     /// Returns the size of the graph (number of nodes).
     fn size(&self) -> usize;
 
     /// Returns the number of parents of each node in the graph.
     fn degree(&self) -> usize;
 
+    /// Returns the number of actual parents of `node`. This is `degree()` for most nodes, but may
+    /// be less for early nodes which have fewer predecessors to choose from; `parents` still fills
+    /// `degree()` entries for those, padding with node `0`.
+    fn degree_for_node(&self, _node: usize) -> usize {
+        self.degree()
+    }
+
+    /// Returns the number of base (DRG) parents of each node. These come first in the list
+    /// filled by `parents`.
+    fn base_degree(&self) -> usize {
+        self.degree() - self.expansion_degree()
+    }
+
+    /// Returns the number of expander parents of each node. These follow the base parents in the
+    /// list filled by `parents`.
+    fn expansion_degree(&self) -> usize {
+        0
+    }
+
     fn new(
         nodes: usize,
         base_degree: usize,
@@ -64,6 +225,8 @@ pub trait Graph<H: Hasher>: Debug + Clone + PartialEq + Eq {
         porep_id: PoRepID,
         api_version: ApiVersion,
     ) -> Result<Self>;
+    /// Returns the seed parent generation is derived from, so that an equivalent graph can be
+    /// reconstructed for verification.
     fn seed(&self) -> [u8; 28];
 
     /// Creates the encoding key.
@@ -82,6 +245,18 @@ pub fn graph_height<U: Unsigned>(number_of_leafs: usize) -> usize {
     get_merkle_tree_row_count(number_of_leafs, U::to_usize())
 }
 
+/// The parameters which fully determine the parents of every node of a graph, independent of the
+/// hasher used over it.
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Serialize, Deserialize)]
+pub struct GraphTopology {
+    pub nodes: usize,
+    pub base_degree: usize,
+    pub expansion_degree: usize,
+    /// The seed bytes, i.e. the little-endian encoding of the seed words (see `seed_to_bytes`).
+    pub seed: [u8; 28],
+    pub api_version: ApiVersion,
+}
+
 /// Bucket sampling algorithm.
 #[derive(Clone, Debug, PartialEq, Eq, Copy)]
 pub struct BucketGraph<H: Hasher> {
@@ -92,6 +267,59 @@ pub struct BucketGraph<H: Hasher> {
     _h: PhantomData<H>,
 }
 
+impl<H: Hasher> BucketGraph<H> {
+    /// Reconstructs a graph from its topology, e.g. to verify proofs without the porep id.
+    pub fn from_topology(topology: &GraphTopology) -> Result<Self> {
+        ensure!(
+            topology.expansion_degree == 0,
+            "Expension degree must be zero."
+        );
+        ensure!(topology.base_degree > 0, "Base degree must not be zero.");
+
+        // The number of metagraph nodes must be less than `2u64^54` as to not incur rounding errors
+        // when casting metagraph node indexes from `u64` to `f64` during parent generation.
+        let m_prime = topology.base_degree - 1;
+        let n_metagraph_nodes = (topology.nodes as u64).checked_mul(m_prime as u64);
+        ensure!(
+            matches!(n_metagraph_nodes, Some(n) if n <= 1u64 << 54),
+            "The number of metagraph nodes must be precisely castable to `f64`"
+        );
+
+        Ok(BucketGraph {
+            nodes: topology.nodes,
+            base_degree: topology.base_degree,
+            seed: topology.seed,
+            api_version: topology.api_version,
+            _h: PhantomData,
+        })
+    }
+
+    /// Returns the topology of this graph.
+    pub fn topology(&self) -> GraphTopology {
+        GraphTopology {
+            nodes: self.nodes,
+            base_degree: self.base_degree,
+            expansion_degree: 0,
+            seed: self.seed,
+            api_version: self.api_version,
+        }
+    }
+}
+
+/// Graphs are serialized as their topology, which is validated again on deserialization.
+impl<H: Hasher> Serialize for BucketGraph<H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.topology().serialize(serializer)
+    }
+}
+
+impl<'de, H: Hasher> Deserialize<'de> for BucketGraph<H> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let topology = GraphTopology::deserialize(deserializer)?;
+        BucketGraph::from_topology(&topology).map_err(de::Error::custom)
+    }
+}
+
 impl<H: Hasher> ParameterSetMetadata for BucketGraph<H> {
     fn identifier(&self) -> String {
         // NOTE: Seed is not included because it does not influence parameter generation.
@@ -210,6 +438,17 @@ impl<H: Hasher> Graph<H> for BucketGraph<H> {
         self.base_degree
     }
 
+    /// The first node has no parents (it only references itself), the second node only has the
+    /// first node as parent.
+    #[inline]
+    fn degree_for_node(&self, node: usize) -> usize {
+        match node {
+            0 => 0,
+            1 => min(1, self.degree()),
+            _ => self.degree(),
+        }
+    }
+
     fn seed(&self) -> [u8; 28] {
         self.seed
     }
@@ -221,27 +460,370 @@ impl<H: Hasher> Graph<H> for BucketGraph<H> {
         porep_id: PoRepID,
         api_version: ApiVersion,
     ) -> Result<Self> {
-        ensure!(expansion_degree == 0, "Expension degree must be zero.");
+        BucketGraph::from_topology(&GraphTopology {
+            nodes,
+            base_degree,
+            expansion_degree,
+            seed: derive_drg_seed(porep_id),
+            api_version,
+        })
+    }
+}
 
-        // The number of metagraph nodes must be less than `2u64^54` as to not incur rounding errors
-        // when casting metagraph node indexes from `u64` to `f64` during parent generation.
-        let m_prime = base_degree - 1;
-        let n_metagraph_nodes = nodes as u64 * m_prime as u64;
+/// The parents of every node of a graph, as returned by `Graph::parents_all`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParentMatrix {
+    degree: usize,
+    /// Row-major: the parents of `node` are `parents[node * degree..(node + 1) * degree]`.
+    parents: Vec<u32>,
+}
+
+impl ParentMatrix {
+    /// Returns the parents of `node`, as `Graph::parents` would fill them.
+    pub fn row(&self, node: usize) -> &[u32] {
+        &self.parents[node * self.degree..(node + 1) * self.degree]
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// Returns the number of nodes.
+    pub fn len(&self) -> usize {
+        if self.degree == 0 {
+            0
+        } else {
+            self.parents.len() / self.degree
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// Returns `Sha256(degree | parents)`, with the degree as a little-endian `u64` and every
+    /// parent as a little-endian `u32`. A matrix loaded from untrusted storage should be checked
+    /// against a commitment obtained when it was written.
+    pub fn commitment(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(&(self.degree as u64).to_le_bytes());
+        for parent in &self.parents {
+            hasher.update(&parent.to_le_bytes());
+        }
+        hasher.finalize().into()
+    }
+
+    /// Writes the matrix in the format read by `read_from`: the degree as a little-endian `u64`
+    /// followed by every parent as a little-endian `u32`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(&(self.degree as u64).to_le_bytes())?;
+        for parent in &self.parents {
+            writer.write_all(&parent.to_le_bytes())?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a matrix written by `write_to`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let mut degree = [0u8; 8];
+        reader.read_exact(&mut degree)?;
+        let degree = u64::from_le_bytes(degree) as usize;
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
         ensure!(
-            n_metagraph_nodes <= 1u64 << 54,
-            "The number of metagraph nodes must be precisely castable to `f64`"
+            bytes.len() % 4 == 0,
+            "parent matrix has {} trailing bytes",
+            bytes.len() % 4
+        );
+        let parents: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().expect("chunks are 4 bytes")))
+            .collect();
+        ensure!(
+            (degree == 0 && parents.is_empty()) || (degree > 0 && parents.len() % degree == 0),
+            "{} parents do not form rows of degree {}",
+            parents.len(),
+            degree
         );
 
-        let drg_seed = derive_drg_seed(porep_id);
+        Ok(ParentMatrix { degree, parents })
+    }
+}
 
-        Ok(BucketGraph {
-            nodes,
-            base_degree,
-            seed: drg_seed,
-            api_version,
-            _h: PhantomData,
+/// The reverse of a graph's parent relation: for every node, the nodes which have it as a parent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChildrenIndex {
+    /// `children[offsets[node]..offsets[node + 1]]` are the children of `node`.
+    offsets: Vec<usize>,
+    children: Vec<u32>,
+}
+
+impl ChildrenIndex {
+    /// Builds the index by walking the parents of every node of `graph` once.
+    pub fn new<H: Hasher, G: Graph<H>>(graph: &G) -> Result<Self> {
+        let mut parents = vec![0; graph.degree()];
+        let mut unique_parents = Vec::with_capacity(graph.degree());
+        let mut edges = Vec::with_capacity(graph.size() * graph.degree());
+
+        for node in 0..graph.size() {
+            graph.parents(node, &mut parents)?;
+            // A node whose first parent is itself has no parents.
+            if parents.is_empty() || parents[0] as usize == node {
+                continue;
+            }
+
+            // Repeated parents are only recorded once.
+            unique_parents.clear();
+            unique_parents.extend_from_slice(&parents);
+            unique_parents.sort_unstable();
+            unique_parents.dedup();
+            edges.extend(unique_parents.iter().map(|parent| (*parent, node as u32)));
+        }
+
+        let mut offsets = vec![0; graph.size() + 1];
+        for (parent, _) in &edges {
+            offsets[*parent as usize + 1] += 1;
+        }
+        let mut total = 0;
+        for offset in offsets.iter_mut() {
+            total += *offset;
+            *offset = total;
+        }
+
+        let mut next = offsets.clone();
+        let mut children = vec![0; edges.len()];
+        for (parent, child) in edges {
+            children[next[parent as usize]] = child;
+            next[parent as usize] += 1;
+        }
+
+        Ok(ChildrenIndex { offsets, children })
+    }
+
+    /// Returns the nodes which have `node` as a parent, in ascending order.
+    pub fn children(&self, node: usize) -> &[u32] {
+        &self.children[self.offsets[node]..self.offsets[node + 1]]
+    }
+}
+
+/// Statistics over the distances `node - parent` of the parents a `LoggingGraph` returned.
+/// Nodes without parents only count towards `calls`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParentDistanceStats {
+    /// Number of `parents` calls.
+    pub calls: u64,
+    /// Number of parents the distances were taken over.
+    pub parents: u64,
+    pub min: u64,
+    pub max: u64,
+    sum: u64,
+}
+
+impl ParentDistanceStats {
+    /// Returns the mean distance, or `0.0` if no parents were seen.
+    pub fn mean(&self) -> f64 {
+        if self.parents == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.parents as f64
+        }
+    }
+
+    fn record(&mut self, node: usize, parents: &[u32]) {
+        self.calls += 1;
+        if parents.is_empty() || parents[0] as usize == node {
+            return;
+        }
+
+        for parent in parents {
+            let distance = node.saturating_sub(*parent as usize) as u64;
+            if self.parents == 0 || distance < self.min {
+                self.min = distance;
+            }
+            self.max = max(self.max, distance);
+            self.sum += distance;
+            self.parents += 1;
+        }
+    }
+}
+
+/// The number of `parents` calls between two records of a `LoggingGraph` created by `Graph::new`.
+pub const DEFAULT_PARENT_LOG_INTERVAL: u64 = 1 << 20;
+
+/// Receives the statistics accumulated by a `LoggingGraph` every `interval` calls to `parents`.
+pub type ParentStatsSink = Arc<dyn Fn(&ParentDistanceStats) + Send + Sync>;
+
+fn log_parent_stats(stats: &ParentDistanceStats) {
+    info!(
+        "parents of {} nodes: distance min {}, max {}, mean {:.2} over {} parents",
+        stats.calls,
+        stats.min,
+        stats.max,
+        stats.mean(),
+        stats.parents
+    );
+}
+
+/// A graph which logs statistics about the parents it returns, for debugging graph quality in
+/// the field. Every `interval` calls to `parents`, the distance statistics accumulated so far are
+/// logged at info level, or passed to the sink given to `with_sink`. Everything else is delegated
+/// to the wrapped graph.
+#[derive(Clone)]
+pub struct LoggingGraph<G> {
+    graph: G,
+    interval: u64,
+    stats: Arc<Mutex<ParentDistanceStats>>,
+    sink: ParentStatsSink,
+}
+
+impl<G: Debug> Debug for LoggingGraph<G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoggingGraph")
+            .field("graph", &self.graph)
+            .field("interval", &self.interval)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl<G> LoggingGraph<G> {
+    pub fn wrap(graph: G, interval: u64) -> Result<Self> {
+        ensure!(interval > 0, "log interval must not be zero");
+
+        Ok(LoggingGraph {
+            graph,
+            interval,
+            stats: Default::default(),
+            sink: Arc::new(log_parent_stats),
         })
     }
+
+    /// Passes the statistics to `sink` instead of logging them.
+    pub fn with_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&ParentDistanceStats) + Send + Sync + 'static,
+    {
+        self.sink = Arc::new(sink);
+        self
+    }
+
+    pub fn inner(&self) -> &G {
+        &self.graph
+    }
+
+    /// Returns the statistics accumulated so far. Clones of this graph share them.
+    pub fn stats(&self) -> ParentDistanceStats {
+        *self.stats.lock().expect("parent stats lock poisoned")
+    }
+}
+
+impl<G: PartialEq> PartialEq for LoggingGraph<G> {
+    fn eq(&self, other: &Self) -> bool {
+        self.graph == other.graph && self.interval == other.interval
+    }
+}
+
+impl<G: Eq> Eq for LoggingGraph<G> {}
+
+impl<H: Hasher, G: Graph<H>> Graph<H> for LoggingGraph<G> {
+    type Key = G::Key;
+
+    fn expected_size(&self) -> usize {
+        self.graph.expected_size()
+    }
+
+    fn data_size(&self) -> usize {
+        self.graph.data_size()
+    }
+
+    fn merkle_tree_depth<U: 'static + PoseidonArity>(&self) -> u64 {
+        self.graph.merkle_tree_depth::<U>()
+    }
+
+    fn parents(&self, node: usize, parents: &mut [u32]) -> Result<()> {
+        self.graph.parents(node, parents)?;
+
+        let mut stats = self.stats.lock().expect("parent stats lock poisoned");
+        stats.record(node, parents);
+        if stats.calls % self.interval == 0 {
+            let summary = *stats;
+            drop(stats);
+            (self.sink)(&summary);
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.graph.size()
+    }
+
+    fn degree(&self) -> usize {
+        self.graph.degree()
+    }
+
+    fn degree_for_node(&self, node: usize) -> usize {
+        self.graph.degree_for_node(node)
+    }
+
+    fn base_degree(&self) -> usize {
+        self.graph.base_degree()
+    }
+
+    fn expansion_degree(&self) -> usize {
+        self.graph.expansion_degree()
+    }
+
+    fn new(
+        nodes: usize,
+        base_degree: usize,
+        expansion_degree: usize,
+        porep_id: PoRepID,
+        api_version: ApiVersion,
+    ) -> Result<Self> {
+        let graph = G::new(nodes, base_degree, expansion_degree, porep_id, api_version)?;
+        LoggingGraph::wrap(graph, DEFAULT_PARENT_LOG_INTERVAL)
+    }
+
+    fn seed(&self) -> [u8; 28] {
+        self.graph.seed()
+    }
+
+    fn create_key(
+        &self,
+        id: &H::Domain,
+        node: usize,
+        parents: &[u32],
+        parents_data: &[u8],
+        exp_parents_data: Option<&[u8]>,
+    ) -> Result<Self::Key> {
+        self.graph
+            .create_key(id, node, parents, parents_data, exp_parents_data)
+    }
+}
+
+/// Encodes the seven 32-bit words of a DRG seed as the 28 bytes graphs store. Parents are sampled
+/// with a ChaCha8 RNG keyed by the seed bytes followed by the node index, and ChaCha reads its key
+/// as little-endian words, so the words are encoded little-endian on every platform.
+pub fn seed_to_bytes(seed: &[u32; 7]) -> [u8; 28] {
+    let mut bytes = [0u8; 28];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(seed.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+/// Decodes the 28 bytes of a DRG seed into its little-endian 32-bit words. This is the inverse of
+/// `seed_to_bytes`.
+pub fn seed_from_bytes_le(bytes: [u8; 28]) -> [u32; 7] {
+    let mut seed = [0u32; 7];
+    for (word, chunk) in seed.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().expect("chunks are 4 bytes"));
+    }
+    seed
 }
 
 pub fn derive_drg_seed(porep_id: PoRepID) -> [u8; 28] {
@@ -261,6 +843,7 @@ mod tests {
     use generic_array::typenum::{U0, U2, U4, U8};
     use memmap::{MmapMut, MmapOptions};
     use merkletree::store::StoreConfig;
+    use rand_xorshift::XorShiftRng;
 
     use crate::merkle::{
         create_base_merkle_tree, DiskStore, MerkleProofTrait, MerkleTreeTrait, MerkleTreeWrapper,
@@ -349,6 +932,370 @@ mod tests {
         }
     }
 
+    #[test]
+    fn graph_bucket_degrees() {
+        let g = BucketGraph::<Sha256Hasher>::new(64, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+
+        assert_eq!(g.base_degree(), BASE_DEGREE);
+        assert_eq!(g.expansion_degree(), 0);
+        assert_eq!(g.degree(), g.base_degree() + g.expansion_degree());
+    }
+
+    #[test]
+    fn graph_bucket_data_size() {
+        let g = BucketGraph::<Sha256Hasher>::new(64, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+
+        assert_eq!(g.data_size(), g.size() * 32);
+        assert_eq!(g.expected_size(), g.data_size());
+        assert_eq!(g.data_size() as u64, g.sector_size());
+    }
+
+    #[test]
+    fn graph_bucket_degree_for_node() {
+        let g = BucketGraph::<Sha256Hasher>::new(64, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+
+        assert_eq!(g.degree_for_node(0), 0);
+        assert_eq!(g.degree_for_node(1), 1);
+        for node in 2..g.size() {
+            assert_eq!(g.degree_for_node(node), g.degree());
+        }
+    }
+
+    #[test]
+    fn graph_bucket_sample_parents() {
+        let g = BucketGraph::<Sha256Hasher>::new(256, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        // Replay the node choices made by `sample_parents` to check each sample against its node.
+        let mut replay = rng.clone();
+        let samples = g.sample_parents(rng, 100).expect("sample_parents failed");
+        assert_eq!(samples.len(), 100);
+        for sample in samples {
+            let node = replay.gen_range(2..g.size());
+            assert_eq!(sample.len(), g.degree());
+            assert!(sample.iter().all(|&parent| parent < node));
+        }
+    }
+
+    #[test]
+    fn seed_bytes_round_trip() {
+        let words = [
+            0x0403_0201,
+            0x0807_0605,
+            0x0c0b_0a09,
+            0x100f_0e0d,
+            0x1413_1211,
+            0x1817_1615,
+            0x1c1b_1a19,
+        ];
+        let bytes = seed_to_bytes(&words);
+        let expected: Vec<u8> = (1..=28).collect();
+        assert_eq!(&bytes[..], &expected[..]);
+        assert_eq!(seed_from_bytes_le(bytes), words);
+
+        let seed = derive_drg_seed([5; 32]);
+        assert_eq!(seed_to_bytes(&seed_from_bytes_le(seed)), seed);
+    }
+
+    #[test]
+    fn logging_graph_logs_parent_stats() {
+        let inner =
+            BucketGraph::<Sha256Hasher>::new(64, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+                .expect("bucket graph new failed");
+        assert!(LoggingGraph::wrap(inner, 0).is_err());
+        let summaries = Arc::new(Mutex::new(Vec::new()));
+        let sink = summaries.clone();
+        let g = LoggingGraph::wrap(inner, 10)
+            .expect("wrap failed")
+            .with_sink(move |stats| sink.lock().expect("sink lock poisoned").push(*stats));
+
+        let mut parents = vec![0; g.degree()];
+        let mut inner_parents = vec![0; g.degree()];
+        let mut expected = ParentDistanceStats::default();
+        for node in 0..25 {
+            g.parents(node, &mut parents).expect("parents failed");
+            inner
+                .parents(node, &mut inner_parents)
+                .expect("parents failed");
+            assert_eq!(parents, inner_parents);
+            expected.record(node, &parents);
+        }
+
+        let stats = g.stats();
+        assert_eq!(stats, expected);
+        assert_eq!(stats.calls, 25);
+        assert!(stats.min >= 1);
+        assert!(stats.max < 25);
+        assert!(stats.mean() >= stats.min as f64 && stats.mean() <= stats.max as f64);
+
+        // One summary after every 10 calls.
+        let summaries = summaries.lock().expect("sink lock poisoned");
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].calls, 10);
+        assert_eq!(summaries[1].calls, 20);
+    }
+
+    #[test]
+    fn graph_bucket_seed_stability() {
+        // Changing any of these values changes the topology of every DRG derived from the
+        // porep_id, and therefore invalidates all existing replicas and proofs.
+        let mut porep_id = [0u8; 32];
+        porep_id[0] = 5;
+
+        let g =
+            BucketGraph::<Sha256Hasher>::new(2048, BASE_DEGREE, 0, porep_id, ApiVersion::V1_1_0)
+                .expect("bucket graph new failed");
+        assert_eq!(g.seed(), derive_drg_seed(porep_id));
+        assert_eq!(
+            g.seed(),
+            [
+                59, 230, 133, 66, 236, 34, 151, 5, 185, 155, 226, 156, 33, 6, 176, 228, 34, 44,
+                205, 29, 3, 17, 57, 142, 164, 210, 45, 125
+            ]
+        );
+
+        let expected: &[(usize, [u32; BASE_DEGREE])] = &[
+            (0, [0, 0, 0, 0, 0, 0]),
+            (1, [0, 0, 0, 0, 0, 0]),
+            (2, [1, 1, 1, 0, 1, 0]),
+            (3, [2, 1, 1, 2, 2, 2]),
+            (4, [3, 3, 1, 2, 3, 0]),
+            (5, [4, 4, 4, 1, 2, 4]),
+            (6, [5, 4, 5, 5, 5, 2]),
+            (7, [6, 3, 4, 6, 5, 6]),
+            (8, [7, 7, 6, 7, 6, 7]),
+            (9, [8, 8, 4, 8, 8, 7]),
+            (100, [99, 88, 88, 98, 99, 96]),
+            (1000, [999, 971, 998, 999, 999, 999]),
+            (2047, [2046, 1036, 2043, 2044, 2010, 2046]),
+        ];
+
+        let mut parents = vec![0; BASE_DEGREE];
+        for (node, expected_parents) in expected {
+            g.parents(*node, &mut parents).expect("parents failed");
+            assert_eq!(
+                &parents[..],
+                &expected_parents[..],
+                "parents of node {}",
+                node
+            );
+        }
+    }
+
+    #[test]
+    fn graph_bucket_parents_all() {
+        let g = BucketGraph::<Sha256Hasher>::new(512, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+        let matrix = g.parents_all().expect("parents_all failed");
+
+        assert_eq!(matrix.len(), g.size());
+        assert_eq!(matrix.degree(), g.degree());
+
+        let mut parents = vec![0; g.degree()];
+        for node in 0..g.size() {
+            g.parents(node, &mut parents).expect("parents failed");
+            assert_eq!(matrix.row(node), &parents[..], "parents of node {}", node);
+        }
+    }
+
+    #[test]
+    fn graph_bucket_parents_checked() {
+        let g = BucketGraph::<Sha256Hasher>::new(64, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+
+        let mut parents = vec![0; g.degree()];
+        for &len in &[0, 1, g.degree(), g.degree() + 5] {
+            let mut checked = vec![7; len];
+            for node in 0..g.size() {
+                g.parents_checked(node, &mut checked)
+                    .expect("parents_checked failed");
+                g.parents(node, &mut parents).expect("parents failed");
+
+                assert_eq!(checked, parents);
+            }
+        }
+
+        let mut checked = Vec::new();
+        assert!(g.parents_checked(g.size(), &mut checked).is_err());
+    }
+
+    #[test]
+    fn graph_bucket_layering() {
+        let g = BucketGraph::<Sha256Hasher>::new(256, BASE_DEGREE, 0, [3; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+        let layers = g.layering().expect("layering failed");
+
+        let mut layer_of = vec![None; g.size()];
+        for (i, layer) in layers.iter().enumerate() {
+            assert!(!layer.is_empty());
+            for node in layer {
+                assert_eq!(layer_of[*node], None, "node {} is in two layers", node);
+                layer_of[*node] = Some(i);
+            }
+        }
+        assert!(layer_of.iter().all(Option::is_some));
+        assert_eq!(layers[0], vec![0]);
+
+        let mut parents = vec![0; g.degree()];
+        for (i, layer) in layers.iter().enumerate() {
+            for node in layer {
+                g.parents(*node, &mut parents).expect("parents failed");
+                for parent in parents.iter().map(|parent| *parent as usize) {
+                    if parent != *node {
+                        let parent_layer = layer_of[parent].expect("parent has no layer");
+                        assert!(
+                            parent_layer < i,
+                            "parent {} of node {} is not in an earlier layer",
+                            parent,
+                            node
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parent_matrix_write_read_round_trip() {
+        let g = BucketGraph::<Sha256Hasher>::new(128, BASE_DEGREE, 0, [2; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+        let matrix = g.parents_all().expect("parents_all failed");
+
+        let mut bytes = Vec::new();
+        matrix.write_to(&mut bytes).expect("write_to failed");
+        assert_eq!(bytes.len(), 8 + 4 * g.size() * g.degree());
+
+        let read = ParentMatrix::read_from(&bytes[..]).expect("read_from failed");
+        assert_eq!(read, matrix);
+        assert_eq!(read.commitment(), matrix.commitment());
+
+        // Any change to the parents changes the commitment.
+        let mut tampered = bytes.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        let tampered = ParentMatrix::read_from(&tampered[..]).expect("read_from failed");
+        assert_ne!(tampered.commitment(), matrix.commitment());
+
+        // A truncated parent is rejected.
+        assert!(ParentMatrix::read_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn graph_bucket_from_topology() {
+        for &api_version in &[ApiVersion::V1_0_0, ApiVersion::V1_1_0] {
+            let g = BucketGraph::<Sha256Hasher>::new(256, BASE_DEGREE, 0, [3; 32], api_version)
+                .expect("bucket graph new failed");
+            let topology = g.topology();
+            let reconstructed = BucketGraph::<Sha256Hasher>::from_topology(&topology)
+                .expect("from_topology failed");
+
+            assert_eq!(reconstructed, g);
+            assert_eq!(reconstructed.topology(), topology);
+
+            let mut expected = vec![0; g.degree()];
+            let mut actual = vec![0; g.degree()];
+            for node in 0..g.size() {
+                g.parents(node, &mut expected).expect("parents failed");
+                reconstructed
+                    .parents(node, &mut actual)
+                    .expect("parents failed");
+                assert_eq!(actual, expected, "parents of node {} differ", node);
+            }
+        }
+
+        let valid = GraphTopology {
+            nodes: 64,
+            base_degree: BASE_DEGREE,
+            expansion_degree: 0,
+            seed: [0; 28],
+            api_version: ApiVersion::V1_1_0,
+        };
+        let invalid = [
+            GraphTopology {
+                expansion_degree: 1,
+                ..valid
+            },
+            GraphTopology {
+                base_degree: 0,
+                ..valid
+            },
+            GraphTopology {
+                nodes: usize::MAX,
+                ..valid
+            },
+        ];
+        for topology in &invalid {
+            assert!(BucketGraph::<Sha256Hasher>::from_topology(topology).is_err());
+        }
+    }
+
+    #[test]
+    fn graph_bucket_serde_round_trip() {
+        let g =
+            BucketGraph::<PoseidonHasher>::new(256, BASE_DEGREE, 0, [4; 32], ApiVersion::V1_1_0)
+                .expect("bucket graph new failed");
+
+        let json = serde_json::to_string(&g).expect("failed to serialize graph");
+        let deserialized: BucketGraph<PoseidonHasher> =
+            serde_json::from_str(&json).expect("failed to deserialize graph");
+        assert_eq!(deserialized, g);
+
+        let mut expected = vec![0; g.degree()];
+        let mut actual = vec![0; g.degree()];
+        for node in 0..g.size() {
+            g.parents(node, &mut expected).expect("parents failed");
+            deserialized
+                .parents(node, &mut actual)
+                .expect("parents failed");
+            assert_eq!(actual, expected, "parents of node {} differ", node);
+        }
+
+        let mut invalid = g.topology();
+        invalid.expansion_degree = 8;
+        let json = serde_json::to_string(&invalid).expect("failed to serialize topology");
+        assert!(serde_json::from_str::<BucketGraph<PoseidonHasher>>(&json).is_err());
+    }
+
+    #[test]
+    fn graph_bucket_children() {
+        let g = BucketGraph::<Sha256Hasher>::new(256, BASE_DEGREE, 0, [1; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+        let index = ChildrenIndex::new(&g).expect("children index failed");
+
+        let mut parents = vec![0; g.degree()];
+        let mut edges = 0;
+        for node in 1..g.size() {
+            g.parents(node, &mut parents).expect("parents failed");
+            for parent in &parents {
+                assert!(
+                    index.children(*parent as usize).contains(&(node as u32)),
+                    "node {} missing from the children of {}",
+                    node,
+                    parent
+                );
+            }
+
+            let mut unique_parents = parents.clone();
+            unique_parents.sort_unstable();
+            unique_parents.dedup();
+            edges += unique_parents.len();
+        }
+
+        let mut indexed_edges = 0;
+        for node in 0..g.size() {
+            let children = index.children(node);
+            assert!(children.windows(2).all(|w| w[0] < w[1]));
+            assert!(children.iter().all(|child| *child as usize > node));
+            indexed_edges += children.len();
+        }
+        assert_eq!(indexed_edges, edges);
+        assert!(index.children(0).contains(&1));
+    }
+
     #[test]
     fn graph_bucket_sha256() {
         graph_bucket::<Sha256Hasher>();
@@ -13,6 +13,10 @@ pub enum Error {
     BadPieceCommitment,
     #[error("Out of bounds access {} > {}", _0, _1)]
     OutOfBounds(usize, usize),
+    #[error("Node {} is out of bounds for data of length {}", _0, _1)]
+    NodeOutOfBounds(usize, usize),
+    #[error("Data length {} is not a multiple of the node size {}", _0, _1)]
+    UnalignedData(usize, usize),
     #[error("mismatch of data, node_size and nodes {} != {} * {}", _0, _1, _2)]
     InvalidMerkleTreeArgs(usize, usize, usize),
     #[error("{}", _0)]
@@ -41,6 +45,8 @@ pub enum Error {
     FaultySectors(Vec<SectorId>),
     #[error("Invalid parameters file: {}", _0)]
     InvalidParameters(String),
+    #[error("operation was cancelled")]
+    Cancelled,
 }
 
 impl From<Box<dyn Any + Send>> for Error {
@@ -11,6 +11,9 @@
 
 use std::convert::TryInto;
 
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+
 pub mod api_version;
 pub mod cache_key;
 pub mod compound_proof;
@@ -1,25 +1,35 @@
 use std::any::Any;
 use std::fs::File;
 use std::io::Write;
+use std::marker::PhantomData;
 use std::mem::size_of;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::ensure;
-use filecoin_hashers::{Domain, Hasher, PoseidonArity};
+#[cfg(feature = "checked")]
+use blstrs::Scalar as Fr;
+#[cfg(feature = "checked")]
+use ff::PrimeField;
+use filecoin_hashers::{Domain, HashFunction, Hasher, PoseidonArity};
 use generic_array::typenum::{Unsigned, U0};
 use log::trace;
 use merkletree::{
+    hash::Algorithm,
     merkle::{
-        get_merkle_tree_leafs, is_merkle_tree_size_valid, FromIndexedParallelIterator, MerkleTree,
+        get_merkle_tree_leafs, get_merkle_tree_row_count, is_merkle_tree_size_valid,
+        FromIndexedParallelIterator, MerkleTree,
     },
     store::{DiskStore, ExternalReader, LevelCacheStore, ReplicaConfig, Store, StoreConfig},
 };
 use rand::Rng;
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator, ParallelSlice};
 
 use crate::{
     error::{Error, Result},
-    merkle::{DiskTree, LCMerkleTree, LCStore, LCTree, MerkleTreeTrait, MerkleTreeWrapper},
+    merkle::{
+        DiskTree, LCMerkleTree, LCStore, LCTree, MerkleTreeTrait, MerkleTreeWrapper, VecMerkleTree,
+    },
     util::{data_at_node, default_rows_to_discard, NODE_SIZE},
 };
 
@@ -171,9 +181,43 @@ where
     }
 }
 
+/// Ensures that `size` is a valid number of leaves for a base layer tree of the given shape,
+/// i.e. that it is a power of the base arity, instead of letting the tree be silently padded.
+pub fn validate_base_tree_size<Tree: MerkleTreeTrait>(size: usize) -> Result<()> {
+    let arity = Tree::Arity::to_usize();
+    ensure!(
+        is_merkle_tree_size_valid(size, arity),
+        "Invalid merkle tree size {} given the arity {}: size must be a power of the arity",
+        size,
+        arity
+    );
+
+    Ok(())
+}
+
+/// Checks that the first `size` nodes of `data` are canonical field elements, which hashers like
+/// Poseidon otherwise panic on while building the tree. Every builder taking leaf data runs it.
+#[cfg(feature = "checked")]
+fn ensure_canonical_leaves(data: &[u8], size: usize) -> Result<()> {
+    for i in 0..size {
+        let mut repr = <Fr as PrimeField>::Repr::default();
+        repr.copy_from_slice(data_at_node(data, i)?);
+        ensure!(
+            Fr::from_repr_vartime(repr).is_some(),
+            "leaf {} is not a canonical field element",
+            i
+        );
+    }
+
+    Ok(())
+}
+
 // Note: This method verifies that the tree can be build with the size
 // specified.  If the data on disk is longer, this method is safe to
 // use on the first 'size' nodes.
+//
+// Where the tree is stored is determined by `Tree::Store`: e.g. a `BinaryMerkleTree` is backed by
+// a `DiskStore`, a `BinaryVecMerkleTree` is held in memory. The root does not depend on it.
 pub fn create_base_merkle_tree<Tree: MerkleTreeTrait>(
     config: Option<StoreConfig>,
     size: usize,
@@ -191,10 +235,9 @@ pub fn create_base_merkle_tree<Tree: MerkleTreeTrait>(
         Tree::Arity::to_usize(),
         is_merkle_tree_size_valid(size, Tree::Arity::to_usize())
     );
-    ensure!(
-        is_merkle_tree_size_valid(size, Tree::Arity::to_usize()),
-        "Invalid merkle tree size given the arity"
-    );
+    validate_base_tree_size::<Tree>(size)?;
+    #[cfg(feature = "checked")]
+    ensure_canonical_leaves(data, size)?;
 
     let f = |i| {
         // TODO Replace `expect()` with `context()` (problem is the parallel iterator)
@@ -230,6 +273,52 @@ pub fn create_base_merkle_tree<Tree: MerkleTreeTrait>(
     Ok(Tree::from_merkle(tree))
 }
 
+/// Like `create_base_merkle_tree`, but builds the tree in memory instead of writing it to a
+/// `DiskStore`, which is all small trees (e.g. in tests) need.
+pub fn create_vec_merkle_tree<H: 'static + Hasher, U: 'static + PoseidonArity>(
+    size: usize,
+    data: &[u8],
+) -> Result<VecMerkleTree<H, U>> {
+    create_base_merkle_tree::<VecMerkleTree<H, U>>(None, size, data)
+}
+
+/// Like `create_base_merkle_tree`, but accepts any positive number of leaves, padding them up to
+/// the next power of the arity with `hash_empty()`. Returns the tree and the number of padding
+/// leaves appended.
+pub fn create_padded_merkle_tree<Tree: MerkleTreeTrait>(
+    config: Option<StoreConfig>,
+    size: usize,
+    data: &[u8],
+) -> Result<(Tree, usize)> {
+    ensure!(
+        data.len() == NODE_SIZE * size,
+        Error::InvalidMerkleTreeArgs(data.len(), NODE_SIZE, size)
+    );
+    ensure!(size > 0, "cannot create a merkle tree without leaves");
+
+    let arity = Tree::Arity::to_usize();
+    let mut padded_size = 1usize;
+    while padded_size < size {
+        padded_size = padded_size
+            .checked_mul(arity)
+            .ok_or_else(|| anyhow::anyhow!("cannot pad {} leaves to a power of {}", size, arity))?;
+    }
+    let padding = padded_size - size;
+    if padding == 0 {
+        return Ok((create_base_merkle_tree::<Tree>(config, size, data)?, 0));
+    }
+
+    let empty = <Tree::Hasher as Hasher>::Function::hash_empty();
+    let mut padded = Vec::with_capacity(padded_size * NODE_SIZE);
+    padded.extend_from_slice(data);
+    for _ in 0..padding {
+        padded.extend_from_slice(AsRef::<[u8]>::as_ref(&empty));
+    }
+
+    let tree = create_base_merkle_tree::<Tree>(config, padded_size, &padded)?;
+    Ok((tree, padding))
+}
+
 /// Construct a new level cache merkle tree, given the specified
 /// config.
 ///
@@ -258,6 +347,8 @@ pub fn create_base_lcmerkle_tree<H: Hasher, BaseTreeArity: 'static + PoseidonAri
         data.len() == size * size_of::<H::Domain>(),
         "Invalid data length for merkle tree"
     );
+    #[cfg(feature = "checked")]
+    ensure_canonical_leaves(data, size)?;
 
     let f = |i| {
         let d = data_at_node(data, i)?;
@@ -523,3 +614,322 @@ where
         generate_base_tree::<R, Tree>(rng, nodes, temp_path)
     }
 }
+
+/// Number of node pairs of a binary layer hashed at once by `MerkleBuilder`.
+const HASH_LAYER_BATCH_SIZE: usize = 1024;
+
+/// Builds base merkle trees layer by layer, reporting progress and allowing the build to be
+/// cancelled between layers.
+pub struct MerkleBuilder<'a, H: Hasher, U: PoseidonArity> {
+    progress: Option<Box<dyn FnMut(usize, usize) + 'a>>,
+    cancel: Option<&'a AtomicBool>,
+    _h: PhantomData<(H, U)>,
+}
+
+impl<'a, H: 'static + Hasher, U: 'static + PoseidonArity> Default for MerkleBuilder<'a, H, U> {
+    fn default() -> Self {
+        MerkleBuilder {
+            progress: None,
+            cancel: None,
+            _h: PhantomData,
+        }
+    }
+}
+
+impl<'a, H: 'static + Hasher, U: 'static + PoseidonArity> MerkleBuilder<'a, H, U> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `progress(layers_built, total_layers)` once for every layer, the leaves included.
+    pub fn with_progress<F: FnMut(usize, usize) + 'a>(mut self, progress: F) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Stops the build with `Error::Cancelled` once `cancel` is set, checked before each layer.
+    pub fn with_cancel(mut self, cancel: &'a AtomicBool) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Builds the tree over the `n_nodes` leaves in `data`, which must be a power of the arity.
+    pub fn build(&mut self, n_nodes: usize, data: &[u8]) -> Result<DiskTree<H, U, U0, U0>> {
+        ensure!(
+            data.len() == NODE_SIZE * n_nodes,
+            Error::InvalidMerkleTreeArgs(data.len(), NODE_SIZE, n_nodes)
+        );
+        validate_base_tree_size::<DiskTree<H, U, U0, U0>>(n_nodes)?;
+        #[cfg(feature = "checked")]
+        ensure_canonical_leaves(data, n_nodes)?;
+
+        let arity = U::to_usize();
+        let total_layers = get_merkle_tree_row_count(n_nodes, arity);
+
+        let mut nodes = data
+            .chunks(NODE_SIZE)
+            .map(H::Domain::try_from_bytes)
+            .collect::<Result<Vec<_>>>()?;
+        self.report(1, total_layers);
+
+        let mut layer_start = 0;
+        for layer in 1..total_layers {
+            if self
+                .cancel
+                .map_or(false, |cancel| cancel.load(Ordering::SeqCst))
+            {
+                return Err(Error::Cancelled.into());
+            }
+
+            let layer_len = nodes.len() - layer_start;
+            let next = if arity == 2 {
+                let pairs = nodes[layer_start..]
+                    .chunks(2)
+                    .map(|pair| (pair[0], pair[1]))
+                    .collect::<Vec<_>>();
+                pairs
+                    .par_chunks(HASH_LAYER_BATCH_SIZE)
+                    .flat_map(H::Function::hash_layer)
+                    .collect::<Vec<_>>()
+            } else {
+                nodes[layer_start..]
+                    .par_chunks(arity)
+                    .map(|children| {
+                        let mut hasher = H::Function::default();
+                        hasher.multi_node(children, layer - 1)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            layer_start += layer_len;
+            nodes.extend(next);
+
+            self.report(layer + 1, total_layers);
+        }
+
+        let mut tree_data = Vec::with_capacity(nodes.len() * NODE_SIZE);
+        for node in &nodes {
+            tree_data.extend_from_slice(AsRef::<[u8]>::as_ref(node));
+        }
+
+        DiskTree::<H, U, U0, U0>::from_tree_slice(&tree_data, n_nodes)
+    }
+
+    fn report(&mut self, layers_built: usize, total_layers: usize) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress(layers_built, total_layers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blstrs::Scalar as Fr;
+    use ff::PrimeField;
+    use filecoin_hashers::{poseidon::PoseidonHasher, sha256::Sha256Hasher};
+    use generic_array::typenum::{U2, U8};
+
+    use crate::merkle::{BinaryMerkleTree, MerkleProofTrait, OctMerkleTree};
+
+    #[test]
+    fn test_validate_base_tree_size() {
+        validate_base_tree_size::<BinaryMerkleTree<PoseidonHasher>>(64)
+            .expect("64 is a valid binary tree size");
+        validate_base_tree_size::<OctMerkleTree<PoseidonHasher>>(64)
+            .expect("64 is a valid oct tree size");
+
+        let err = validate_base_tree_size::<OctMerkleTree<PoseidonHasher>>(32)
+            .expect_err("32 is not a valid oct tree size");
+        assert!(err.to_string().contains("32"));
+    }
+
+    #[test]
+    fn test_create_base_merkle_tree_rejects_invalid_size() {
+        let nodes = 4;
+        let data = vec![0u8; NODE_SIZE * nodes];
+        let tree = create_base_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(None, nodes, &data)
+            .expect("failed to create tree with valid size");
+        assert_eq!(tree.leaves(), nodes);
+
+        let nodes = 3;
+        let data = vec![0u8; NODE_SIZE * nodes];
+        let err = create_base_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(None, nodes, &data)
+            .expect_err("tree with invalid size was created");
+        assert!(err
+            .to_string()
+            .contains("Invalid merkle tree size 3 given the arity 2"));
+    }
+
+    #[test]
+    fn test_vec_merkle_tree_matches_disk_merkle_tree() {
+        let nodes = 64;
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|i| Fr::from(i as u64).to_repr().to_vec())
+            .collect();
+
+        let disk_tree =
+            create_base_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(None, nodes, &data)
+                .expect("failed to create disk tree");
+        let vec_tree = create_vec_merkle_tree::<PoseidonHasher, U2>(nodes, &data)
+            .expect("failed to create vec tree");
+        assert_eq!(vec_tree.root(), disk_tree.root());
+
+        let proof = vec_tree.gen_proof(7).expect("failed to generate proof");
+        assert!(proof.validate(7));
+        assert_eq!(proof.root(), disk_tree.root());
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    fn test_create_base_merkle_tree_rejects_non_canonical_leaves() {
+        let nodes = 4;
+        let mut data = vec![0u8; NODE_SIZE * nodes];
+        data[2 * NODE_SIZE..3 * NODE_SIZE].copy_from_slice(&[0xff; NODE_SIZE]);
+
+        let err = create_base_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(None, nodes, &data)
+            .expect_err("tree with non-canonical leaf was created");
+        assert!(err.to_string().contains("leaf 2"), "{}", err);
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    fn test_merkle_builder_rejects_non_canonical_leaves() {
+        let nodes = 8;
+        let mut data = vec![0u8; NODE_SIZE * nodes];
+        data[5 * NODE_SIZE..6 * NODE_SIZE].copy_from_slice(&[0xff; NODE_SIZE]);
+
+        let err = MerkleBuilder::<PoseidonHasher, U8>::new()
+            .build(nodes, &data)
+            .expect_err("tree with non-canonical leaf was built");
+        assert!(err.to_string().contains("leaf 5"), "{}", err);
+    }
+
+    #[test]
+    fn test_merkle_builder_matches_create_base_merkle_tree() {
+        let nodes = 64;
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|i| {
+                let mut node = [0u8; NODE_SIZE];
+                node[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                node
+            })
+            .collect();
+
+        let mut calls = Vec::new();
+        let tree = MerkleBuilder::<PoseidonHasher, U8>::new()
+            .with_progress(|built, total| calls.push((built, total)))
+            .build(nodes, &data)
+            .expect("failed to build tree");
+        let expected = create_base_merkle_tree::<OctMerkleTree<PoseidonHasher>>(None, nodes, &data)
+            .expect("failed to create tree");
+
+        assert_eq!(tree.root(), expected.root());
+        assert_eq!(tree.leaves(), nodes);
+        // The leaves, one layer of 8 nodes and the root.
+        assert_eq!(calls, vec![(1, 3), (2, 3), (3, 3)]);
+
+        let proof = tree.gen_proof(17).expect("failed to generate proof");
+        assert!(proof.validate(17));
+    }
+
+    #[test]
+    fn test_merkle_builder_binary_matches_create_base_merkle_tree() {
+        fn check<H: 'static + Hasher>() {
+            // Large enough for the first layer to span several hash_layer batches.
+            let nodes = 4 * HASH_LAYER_BATCH_SIZE;
+            let data: Vec<u8> = (0..nodes)
+                .flat_map(|i| {
+                    let mut node = [0u8; NODE_SIZE];
+                    node[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                    node
+                })
+                .collect();
+
+            let tree = MerkleBuilder::<H, U2>::new()
+                .build(nodes, &data)
+                .expect("failed to build tree");
+            let expected = create_base_merkle_tree::<BinaryMerkleTree<H>>(None, nodes, &data)
+                .expect("failed to create tree");
+            assert_eq!(tree.root(), expected.root());
+        }
+
+        check::<PoseidonHasher>();
+        check::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn test_merkle_builder_cancel() {
+        let nodes = 64;
+        let data = vec![0u8; NODE_SIZE * nodes];
+
+        let cancel = AtomicBool::new(true);
+        let err = MerkleBuilder::<PoseidonHasher, U2>::new()
+            .with_cancel(&cancel)
+            .build(nodes, &data)
+            .expect_err("cancelled build succeeded");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::Cancelled)
+        ));
+
+        // Cancelling while the second layer is reported stops before the third one is built.
+        let cancel = AtomicBool::new(false);
+        let mut layers_built = 0;
+        let err = MerkleBuilder::<PoseidonHasher, U2>::new()
+            .with_cancel(&cancel)
+            .with_progress(|built, _| {
+                layers_built = built;
+                if built == 2 {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+            })
+            .build(nodes, &data)
+            .expect_err("cancelled build succeeded");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::Cancelled)
+        ));
+        assert_eq!(layers_built, 2);
+    }
+
+    #[test]
+    fn test_create_padded_merkle_tree() {
+        let nodes = 3;
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|i| {
+                let mut node = [0u8; NODE_SIZE];
+                node[0] = i as u8 + 1;
+                node
+            })
+            .collect();
+
+        let (tree, padding) =
+            create_padded_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(None, nodes, &data)
+                .expect("failed to create padded tree");
+        assert_eq!(padding, 1);
+        assert_eq!(tree.leaves(), 4);
+
+        let proof = tree.gen_proof(3).expect("failed to generate proof");
+        assert!(proof.validate(3));
+        assert_eq!(
+            proof.leaf(),
+            <PoseidonHasher as Hasher>::Function::hash_empty()
+        );
+
+        // Padding is deterministic.
+        let (again, _) =
+            create_padded_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(None, nodes, &data)
+                .expect("failed to create padded tree");
+        assert_eq!(again.root(), tree.root());
+
+        let (unpadded, padding) = create_padded_merkle_tree::<OctMerkleTree<PoseidonHasher>>(
+            None,
+            8,
+            &vec![0u8; 8 * NODE_SIZE],
+        )
+        .expect("failed to create tree");
+        assert_eq!(padding, 0);
+        assert_eq!(unpadded.leaves(), 8);
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::fs::File;
 
-pub use merkletree::store::{DiskStore, ExternalReader, Store};
+pub use merkletree::store::{DiskStore, ExternalReader, Store, VecStore};
 
 use filecoin_hashers::Hasher;
 use generic_array::typenum::{U0, U2, U4, U8};
@@ -10,10 +10,12 @@ use merkletree::store::LevelCacheStore;
 
 mod builders;
 mod proof;
+mod sparse;
 mod tree;
 
 pub use builders::*;
 pub use proof::*;
+pub use sparse::*;
 pub use tree::*;
 
 pub type LCStore<E> = LevelCacheStore<E, File>;
@@ -22,12 +24,16 @@ pub type MerkleStore<T> = DiskStore<T>;
 
 pub type DiskTree<H, U, V, W> = MerkleTreeWrapper<H, DiskStore<<H as Hasher>::Domain>, U, V, W>;
 pub type LCTree<H, U, V, W> = MerkleTreeWrapper<H, LCStore<<H as Hasher>::Domain>, U, V, W>;
+/// A tree held entirely in memory, e.g. for small trees in tests which need not touch the disk.
+pub type VecTree<H, U, V, W> = MerkleTreeWrapper<H, VecStore<<H as Hasher>::Domain>, U, V, W>;
 
 pub type MerkleTree<H, U> = DiskTree<H, U, U0, U0>;
 pub type LCMerkleTree<H, U> = LCTree<H, U, U0, U0>;
+pub type VecMerkleTree<H, U> = VecTree<H, U, U0, U0>;
 
 pub type BinaryMerkleTree<H> = MerkleTree<H, U2>;
 pub type BinaryLCMerkleTree<H> = LCMerkleTree<H, U2>;
+pub type BinaryVecMerkleTree<H> = VecMerkleTree<H, U2>;
 
 pub type BinarySubMerkleTree<H> = DiskTree<H, U2, U2, U0>;
 
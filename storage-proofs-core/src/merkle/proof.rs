@@ -69,7 +69,7 @@ pub trait MerkleProofTrait: Clone + Serialize + DeserializeOwned + Debug + Sync
             return false;
         }
 
-        node == self.path_index()
+        self.checked_path_index() == Some(node)
     }
 
     fn validate_data(&self, data: <Self::Hasher as Hasher>::Domain) -> bool {
@@ -92,8 +92,34 @@ pub trait MerkleProofTrait: Clone + Serialize + DeserializeOwned + Debug + Sync
             .fold(0, |acc, (_, index)| (acc * Self::Arity::to_usize()) + index)
     }
 
+    /// Like `path_index`, but returns `None` instead of overflowing when the path
+    /// does not describe an index representable as `usize`.
+    fn checked_path_index(&self) -> Option<usize> {
+        self.path()
+            .iter()
+            .rev()
+            .try_fold(0usize, |acc, (_, index)| {
+                acc.checked_mul(Self::Arity::to_usize())?
+                    .checked_add(*index)
+            })
+    }
+
+    /// Returns true if this path opens leaf `challenge`: each element's index must be the
+    /// matching digit of `challenge`, least significant first, in the arity of the element's layer
+    /// (one more than its number of sibling hashes, as sub and top tree layers differ from the
+    /// base). `challenge` must have no digits left over once the path is consumed, i.e. it must
+    /// be within the tree.
     fn proves_challenge(&self, challenge: usize) -> bool {
-        self.path_index() == challenge
+        let mut index_in_layer = challenge;
+        for (hashes, index) in self.path() {
+            let arity = hashes.len() + 1;
+            if index != index_in_layer % arity {
+                return false;
+            }
+            index_in_layer /= arity;
+        }
+
+        index_in_layer == 0
     }
 
     /// Calcluates the exected length of the full path, given the number of leaves in the base layer.
@@ -164,7 +190,7 @@ macro_rules! forward_method {
     };
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct InclusionPath<H: Hasher, Arity: PoseidonArity> {
     #[serde(bound(
         serialize = "H::Domain: Serialize",
@@ -212,9 +238,15 @@ impl<H: Hasher, Arity: PoseidonArity> InclusionPath<H, Arity> {
             .rev()
             .fold(0, |acc, p| (acc * Arity::to_usize()) + p.index)
     }
+
+    pub fn checked_path_index(&self) -> Option<usize> {
+        self.path.iter().rev().try_fold(0usize, |acc, p| {
+            acc.checked_mul(Arity::to_usize())?.checked_add(p.index)
+        })
+    }
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PathElement<H: Hasher, Arity: PoseidonArity> {
     #[serde(bound(
         serialize = "H::Domain: Serialize",
@@ -227,7 +259,7 @@ pub struct PathElement<H: Hasher, Arity: PoseidonArity> {
 }
 
 /// Representation of a merkle proof.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MerkleProof<
     H: Hasher,
     BaseArity: PoseidonArity,
@@ -293,9 +325,13 @@ impl<
     fn path_index(&self) -> usize {
         forward_method!(self.data, path_index)
     }
+
+    fn checked_path_index(&self) -> Option<usize> {
+        forward_method!(self.data, checked_path_index)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 enum ProofData<
     H: Hasher,
     BaseArity: PoseidonArity,
@@ -319,7 +355,7 @@ enum ProofData<
     Top(TopProof<H, BaseArity, SubTreeArity, TopTreeArity>),
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct SingleProof<H: Hasher, Arity: PoseidonArity> {
     /// Root of the merkle tree.
     #[serde(bound(
@@ -347,7 +383,7 @@ impl<H: Hasher, Arity: PoseidonArity> SingleProof<H, Arity> {
     }
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct SubProof<H: Hasher, BaseArity: PoseidonArity, SubTreeArity: PoseidonArity> {
     #[serde(bound(
         serialize = "H::Domain: Serialize",
@@ -391,7 +427,7 @@ impl<H: Hasher, BaseArity: PoseidonArity, SubTreeArity: PoseidonArity>
     }
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct TopProof<
     H: Hasher,
     BaseArity: PoseidonArity,
@@ -458,11 +494,13 @@ impl<
         TopTreeArity: PoseidonArity,
     > MerkleProof<H, BaseArity, SubTreeArity, TopTreeArity>
 {
+    /// Builds a placeholder proof with a path of height `n`, shaped like an actual proof of a
+    /// tree of `BaseArity`: each level holds the `BaseArity - 1` siblings of the opened node.
     pub fn new(n: usize) -> Self {
         let root = Default::default();
         let leaf = Default::default();
         let path_elem = PathElement {
-            hashes: vec![Default::default(); BaseArity::to_usize()],
+            hashes: vec![Default::default(); BaseArity::to_usize() - 1],
             index: 0,
             _arity: Default::default(),
         };
@@ -473,6 +511,66 @@ impl<
     }
 }
 
+impl<H: Hasher, Arity: PoseidonArity> MerkleProof<H, Arity> {
+    /// Builds a single tree proof from its path, in the format returned by `path`: for each level
+    /// from the leaves up, the sibling hashes and the index of the node among its siblings.
+    pub fn from_path(path: Vec<(Vec<H::Domain>, usize)>, root: H::Domain, leaf: H::Domain) -> Self {
+        let path = path
+            .into_iter()
+            .map(|(hashes, index)| PathElement {
+                hashes,
+                index,
+                _arity: PhantomData,
+            })
+            .collect::<Vec<_>>();
+
+        MerkleProof {
+            data: ProofData::Single(SingleProof::new(path.into(), root, leaf)),
+        }
+    }
+}
+
+/// Builds an arbitrary domain element, falling back to the default one for bytes which are not a
+/// valid element of the domain.
+#[cfg(feature = "arbitrary")]
+pub fn arbitrary_domain<H: Hasher>(
+    u: &mut arbitrary::Unstructured<'_>,
+) -> arbitrary::Result<H::Domain> {
+    let bytes: [u8; 32] = u.arbitrary()?;
+    Ok(<H::Domain as filecoin_hashers::Domain>::try_from_bytes(&bytes).unwrap_or_default())
+}
+
+/// Builds structurally valid single tree proofs with arbitrary hashes, indices, root and leaf.
+#[cfg(feature = "arbitrary")]
+impl<'a, H: 'static + Hasher, BaseArity: 'static + PoseidonArity> arbitrary::Arbitrary<'a>
+    for MerkleProof<H, BaseArity>
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let arity = BaseArity::to_usize();
+        let height: usize = u.int_in_range(0..=32)?;
+
+        let mut path = Vec::with_capacity(height);
+        for _ in 0..height {
+            let mut hashes = Vec::with_capacity(arity - 1);
+            for _ in 0..arity - 1 {
+                hashes.push(arbitrary_domain::<H>(u)?);
+            }
+            path.push(PathElement {
+                hashes,
+                index: u.int_in_range(0..=arity - 1)?,
+                _arity: PhantomData,
+            });
+        }
+
+        let root = arbitrary_domain::<H>(u)?;
+        let leaf = arbitrary_domain::<H>(u)?;
+
+        Ok(MerkleProof {
+            data: ProofData::Single(SingleProof::new(path.into(), root, leaf)),
+        })
+    }
+}
+
 /// Converts a merkle_light proof to a SingleProof
 fn proof_to_single<H: Hasher, Arity: PoseidonArity, TargetArity: PoseidonArity>(
     proof: &merkletree::proof::Proof<H::Domain, Arity>,
@@ -543,6 +641,10 @@ impl<H: Hasher, Arity: 'static + PoseidonArity> SingleProof<H, Arity> {
     fn path_index(&self) -> usize {
         self.path.path_index()
     }
+
+    fn checked_path_index(&self) -> Option<usize> {
+        self.path.checked_path_index()
+    }
 }
 
 impl<H: Hasher, Arity: 'static + PoseidonArity, SubTreeArity: 'static + PoseidonArity>
@@ -605,6 +707,15 @@ impl<H: Hasher, Arity: 'static + PoseidonArity, SubTreeArity: 'static + Poseidon
 
         (sub_proof_index * base_proof_leaves) + self.base_proof.path_index()
     }
+
+    fn checked_path_index(&self) -> Option<usize> {
+        let base_proof_leaves = checked_leaves::<Arity>(self.base_proof.len())?;
+
+        self.sub_proof
+            .checked_path_index()?
+            .checked_mul(base_proof_leaves)?
+            .checked_add(self.base_proof.checked_path_index()?)
+    }
 }
 
 impl<
@@ -693,6 +804,29 @@ impl<
             + (top_proof_index * sub_proof_leaves)
             + self.base_proof.path_index()
     }
+
+    fn checked_path_index(&self) -> Option<usize> {
+        let base_proof_leaves = checked_leaves::<Arity>(self.base_proof.len())?;
+        let sub_proof_leaves = base_proof_leaves.checked_mul(SubTreeArity::to_usize())?;
+
+        let sub_offset = self
+            .sub_proof
+            .checked_path_index()?
+            .checked_mul(base_proof_leaves)?;
+        let top_offset = self
+            .top_proof
+            .checked_path_index()?
+            .checked_mul(sub_proof_leaves)?;
+
+        sub_offset
+            .checked_add(top_offset)?
+            .checked_add(self.base_proof.checked_path_index()?)
+    }
+}
+
+/// Number of leaves covered by a path of the given height, or `None` on overflow.
+fn checked_leaves<Arity: Unsigned>(height: usize) -> Option<usize> {
+    (0..height).try_fold(1usize, |acc, _| acc.checked_mul(Arity::to_usize()))
 }
 
 #[cfg(test)]
@@ -733,6 +867,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn proves_challenge_rejects_leftover_high_bits() {
+        let nodes = 8;
+        let mut rng = thread_rng();
+        let (_, tree) = generate_tree::<MerkleTreeWrapper<PoseidonHasher, DiskStore<_>, U2>, _>(
+            &mut rng, nodes, None,
+        );
+
+        let proof = tree.gen_proof(3).expect("gen_proof failure");
+        assert_eq!(proof.path().len(), 3);
+        assert!(proof.proves_challenge(3));
+        assert!(!proof.proves_challenge(2));
+
+        // The low bits match, but the tree has no leaf at these indices.
+        assert!(!proof.proves_challenge(3 + nodes));
+        assert!(!proof.proves_challenge(3 | 1 << 40));
+        assert!(!proof.proves_challenge(usize::MAX));
+    }
+
     #[test]
     fn merklepath_poseidon_2() {
         merklepath::<
@@ -901,4 +1054,28 @@ mod tests {
             >,
         >();
     }
+
+    #[test]
+    fn checked_path_index_overflow() {
+        let path_elem = PathElement::<Sha256Hasher, U8> {
+            hashes: vec![Default::default(); 7],
+            index: usize::MAX,
+            _arity: Default::default(),
+        };
+        let proof = MerkleProof::<Sha256Hasher, U8> {
+            data: ProofData::Single(SingleProof::new(
+                vec![path_elem; 2].into(),
+                Default::default(),
+                Default::default(),
+            )),
+        };
+
+        assert_eq!(proof.checked_path_index(), None);
+        assert!(!proof.proves_challenge(usize::MAX));
+        assert!(!proof.validate(usize::MAX));
+
+        let proof = MerkleProof::<Sha256Hasher, U8>::new(3);
+        assert_eq!(proof.checked_path_index(), Some(0));
+        assert!(proof.proves_challenge(0));
+    }
 }
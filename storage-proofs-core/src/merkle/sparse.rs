@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use anyhow::{ensure, Result};
+use filecoin_hashers::{HashFunction, Hasher};
+use generic_array::typenum::U2;
+
+use crate::merkle::MerkleProof;
+
+/// A binary merkle tree over `2^height` leaves, of which only those which were set are stored.
+/// All other leaves hold `hash_empty()`, so the tree can prove both that a value is present at an
+/// index and that nothing was set there.
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTree<H: Hasher> {
+    height: usize,
+    /// `empty[level]` is the root of a subtree of height `level` without any leaves set.
+    empty: Vec<H::Domain>,
+    /// Nodes which differ from the empty subtree, keyed by `(level, index within the level)`.
+    nodes: HashMap<(usize, usize), H::Domain>,
+}
+
+impl<H: Hasher> SparseMerkleTree<H> {
+    pub fn new(height: usize) -> Result<Self> {
+        ensure!(
+            height < usize::BITS as usize,
+            "sparse merkle tree height {} is too large",
+            height
+        );
+
+        let mut empty = Vec::with_capacity(height + 1);
+        empty.push(H::Function::hash_empty());
+        for level in 0..height {
+            empty.push(H::Function::hash2(&empty[level], &empty[level]));
+        }
+
+        Ok(SparseMerkleTree {
+            height,
+            empty,
+            nodes: HashMap::new(),
+        })
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn leaves(&self) -> usize {
+        1 << self.height
+    }
+
+    pub fn root(&self) -> H::Domain {
+        self.node(self.height, 0)
+    }
+
+    /// Returns the leaf at `index`, which is `hash_empty()` unless it was set.
+    pub fn leaf(&self, index: usize) -> H::Domain {
+        self.node(0, index)
+    }
+
+    /// Sets the leaf at `index` and updates all nodes on its path to the root.
+    pub fn update(&mut self, index: usize, leaf: H::Domain) -> Result<()> {
+        ensure!(
+            index < self.leaves(),
+            "index {} is out of bounds for a tree with {} leaves",
+            index,
+            self.leaves()
+        );
+
+        self.set_node(0, index, leaf);
+        let mut index = index;
+        for level in 0..self.height {
+            let parent =
+                H::Function::hash2(&self.node(level, index & !1), &self.node(level, index | 1));
+            index >>= 1;
+            self.set_node(level + 1, index, parent);
+        }
+
+        Ok(())
+    }
+
+    /// Creates a proof of the leaf at `index`. For a leaf which was never set, the proven leaf is
+    /// `hash_empty()`, proving that the index is not a member of the tree.
+    pub fn gen_proof(&self, index: usize) -> Result<MerkleProof<H, U2>> {
+        ensure!(
+            index < self.leaves(),
+            "index {} is out of bounds for a tree with {} leaves",
+            index,
+            self.leaves()
+        );
+
+        let path = (0..self.height)
+            .map(|level| {
+                let node = index >> level;
+                (vec![self.node(level, node ^ 1)], node & 1)
+            })
+            .collect();
+
+        Ok(MerkleProof::from_path(path, self.root(), self.leaf(index)))
+    }
+
+    fn node(&self, level: usize, index: usize) -> H::Domain {
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or(self.empty[level])
+    }
+
+    fn set_node(&mut self, level: usize, index: usize, node: H::Domain) {
+        if node == self.empty[level] {
+            self.nodes.remove(&(level, index));
+        } else {
+            self.nodes.insert((level, index), node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::{poseidon::PoseidonHasher, sha256::Sha256Hasher, Domain};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::merkle::{
+        create_base_merkle_tree, BinaryMerkleTree, MerkleProofTrait, MerkleTreeTrait,
+    };
+    use crate::TEST_SEED;
+
+    fn test_sparse_membership<H: 'static + Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        let mut tree = SparseMerkleTree::<H>::new(40).expect("sparse tree new failed");
+        let empty_root = tree.root();
+
+        let members = [0, 1, 7, 1 << 20, (1 << 40) - 1];
+        let values: Vec<H::Domain> = members
+            .iter()
+            .map(|_| H::Domain::random(&mut *rng))
+            .collect();
+        for (index, value) in members.iter().zip(values.iter()) {
+            tree.update(*index, *value).expect("update failed");
+        }
+        assert_ne!(tree.root(), empty_root);
+
+        for (index, value) in members.iter().zip(values.iter()) {
+            let proof = tree.gen_proof(*index).expect("gen_proof failed");
+            assert!(proof.validate(*index));
+            assert!(proof.validate_data(*value));
+            assert_eq!(proof.root(), tree.root());
+        }
+
+        for index in &[2, 6, 1 << 21, (1 << 40) - 2] {
+            let proof = tree.gen_proof(*index).expect("gen_proof failed");
+            assert!(proof.validate(*index));
+            assert!(proof.validate_data(H::Function::hash_empty()));
+            assert_eq!(proof.root(), tree.root());
+        }
+
+        assert!(tree.gen_proof(1 << 40).is_err());
+        assert!(tree.update(1 << 40, values[0]).is_err());
+
+        // Resetting every leaf restores the empty tree.
+        for index in &members {
+            tree.update(*index, H::Function::hash_empty())
+                .expect("update failed");
+        }
+        assert_eq!(tree.root(), empty_root);
+    }
+
+    #[test]
+    fn sparse_membership_sha256() {
+        test_sparse_membership::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn sparse_membership_poseidon() {
+        test_sparse_membership::<PoseidonHasher>();
+    }
+
+    #[test]
+    fn sparse_matches_dense_tree() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        let leaves: Vec<_> = (0..16)
+            .map(|_| <Sha256Hasher as Hasher>::Domain::random(&mut *rng))
+            .collect();
+        let mut data = Vec::with_capacity(16 * 32);
+        for leaf in &leaves {
+            data.extend_from_slice(AsRef::<[u8]>::as_ref(leaf));
+        }
+        let dense: BinaryMerkleTree<Sha256Hasher> =
+            create_base_merkle_tree::<BinaryMerkleTree<Sha256Hasher>>(None, 16, &data)
+                .expect("create_base_merkle_tree failed");
+
+        let mut sparse = SparseMerkleTree::<Sha256Hasher>::new(4).expect("sparse tree new failed");
+        for (index, leaf) in leaves.iter().enumerate() {
+            sparse.update(index, *leaf).expect("update failed");
+        }
+
+        assert_eq!(sparse.root(), dense.root());
+    }
+}
@@ -6,7 +6,7 @@ use std::ops::{Deref, DerefMut};
 
 use anyhow::Result;
 use filecoin_hashers::{Hasher, PoseidonArity};
-use generic_array::typenum::U0;
+use generic_array::typenum::{Unsigned, U0};
 use merkletree::{
     hash::Hashable,
     merkle::{FromIndexedParallelIterator, MerkleTree},
@@ -32,6 +32,10 @@ pub trait MerkleTreeTrait: Send + Sync + Debug {
 
     /// Print a unique name for this configuration.
     fn display() -> String;
+    /// Returns the arity of the base layer of the tree.
+    fn arity() -> usize {
+        Self::Arity::to_usize()
+    }
     /// Returns the root hash of the tree.
     fn root(&self) -> <Self::Hasher as Hasher>::Domain;
     /// Creates a merkle proof of the node at the given index.
@@ -350,3 +354,19 @@ impl<
         &mut self.inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::poseidon::PoseidonHasher;
+
+    use crate::merkle::{BinaryMerkleTree, OctMerkleTree, QuadMerkleTree};
+
+    #[test]
+    fn test_arity() {
+        assert_eq!(BinaryMerkleTree::<PoseidonHasher>::arity(), 2);
+        assert_eq!(QuadMerkleTree::<PoseidonHasher>::arity(), 4);
+        assert_eq!(OctMerkleTree::<PoseidonHasher>::arity(), 8);
+    }
+}
@@ -219,10 +219,23 @@ fn ensure_ancestor_dirs_exist(cache_entry_path: PathBuf) -> Result<PathBuf> {
 }
 
 pub trait ParameterSetMetadata {
+    /// Version of the `identifier` format. Bump this whenever the format of the identifier
+    /// changes, so that cache entries written under the old format can never collide with new ones.
+    fn schema_version() -> u32
+    where
+        Self: Sized,
+    {
+        1
+    }
     fn identifier(&self) -> String;
     fn sector_size(&self) -> u64;
 }
 
+/// Prefixes a parameter set identifier with the schema version of its format.
+pub fn versioned_identifier(schema_version: u32, identifier: &str) -> String {
+    format!("schema-v{}-{}", schema_version, identifier)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CacheEntryMetadata {
     pub sector_size: u64,
@@ -651,3 +664,19 @@ where
     ensure_parent(file_path)?;
     f(&mut open_file(file_path)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versioned_identifier_differs_across_schema_versions() {
+        let identifier = "drgporep::PublicParams{graph: test}";
+
+        let v1 = versioned_identifier(1, identifier);
+        let v2 = versioned_identifier(2, identifier);
+
+        assert_ne!(v1, v2);
+        assert_eq!(v1, versioned_identifier(1, identifier));
+    }
+}
@@ -1,8 +1,14 @@
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::marker::PhantomData;
 use std::path::Path;
 
+use filecoin_hashers::Domain;
 use memmap::{MmapMut, MmapOptions};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::util::NODE_SIZE;
 
 pub fn setup_replica(data: &[u8], replica_path: &Path) -> MmapMut {
     let mut f = OpenOptions::new()
@@ -20,6 +26,38 @@ pub fn setup_replica(data: &[u8], replica_path: &Path) -> MmapMut {
     }
 }
 
+/// A deterministic source of domain elements, for building reproducible test and benchmark data
+/// from a single seed instead of threading an RNG through every call site.
+#[derive(Debug, Clone)]
+pub struct SeededDomainGen<D: Domain> {
+    rng: ChaCha8Rng,
+    _d: PhantomData<D>,
+}
+
+impl<D: Domain> SeededDomainGen<D> {
+    pub fn new(seed: [u8; 32]) -> Self {
+        SeededDomainGen {
+            rng: ChaCha8Rng::from_seed(seed),
+            _d: PhantomData,
+        }
+    }
+
+    /// Returns the next element of the stream.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> D {
+        D::random(&mut self.rng)
+    }
+
+    /// Returns the bytes of the next `n` elements of the stream, e.g. as replica data.
+    pub fn fill(&mut self, n: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(n * NODE_SIZE);
+        for _ in 0..n {
+            data.extend_from_slice(AsRef::<[u8]>::as_ref(&self.next()));
+        }
+        data
+    }
+}
+
 #[macro_export]
 macro_rules! table_tests {
     ($property_test_func:ident {
@@ -34,3 +72,30 @@ macro_rules! table_tests {
         )+
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::poseidon::PoseidonDomain;
+
+    #[test]
+    fn test_seeded_domain_gen() {
+        let seed = [3u8; 32];
+
+        let mut a = SeededDomainGen::<PoseidonDomain>::new(seed);
+        let mut b = SeededDomainGen::<PoseidonDomain>::new(seed);
+        let from_a: Vec<PoseidonDomain> = (0..8).map(|_| a.next()).collect();
+        let from_b: Vec<PoseidonDomain> = (0..8).map(|_| b.next()).collect();
+        assert_eq!(from_a, from_b);
+
+        let mut other = SeededDomainGen::<PoseidonDomain>::new([4u8; 32]);
+        assert_ne!(other.next(), from_a[0]);
+
+        let data = SeededDomainGen::<PoseidonDomain>::new(seed).fill(8);
+        assert_eq!(data.len(), 8 * 32);
+        for (chunk, el) in data.chunks(32).zip(from_a.iter()) {
+            assert_eq!(chunk, AsRef::<[u8]>::as_ref(el));
+        }
+    }
+}
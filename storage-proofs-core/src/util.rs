@@ -19,14 +19,23 @@ pub fn data_at_node_offset(v: usize) -> usize {
 
 /// Returns the byte slice representing one node (of uniform size, NODE_SIZE) at position v in data.
 pub fn data_at_node(data: &[u8], v: usize) -> anyhow::Result<&[u8]> {
-    let offset = data_at_node_offset(v);
+    let end = v
+        .checked_mul(NODE_SIZE)
+        .and_then(|offset| offset.checked_add(NODE_SIZE))
+        .filter(|end| *end <= data.len())
+        .ok_or(Error::NodeOutOfBounds(v, data.len()))?;
 
+    Ok(&data[end - NODE_SIZE..end])
+}
+
+/// Checks that data consists of whole nodes and returns the number of nodes it contains.
+pub fn assert_node_aligned(data: &[u8]) -> anyhow::Result<usize> {
     ensure!(
-        offset + NODE_SIZE <= data.len(),
-        Error::OutOfBounds(offset + NODE_SIZE, data.len())
+        data.len() % NODE_SIZE == 0,
+        Error::UnalignedData(data.len(), NODE_SIZE)
     );
 
-    Ok(&data[offset..offset + NODE_SIZE])
+    Ok(data.len() / NODE_SIZE)
 }
 
 /// Converts bytes into their bit representation, in little endian format.
@@ -193,6 +202,46 @@ mod tests {
 
     use crate::TEST_SEED;
 
+    #[test]
+    fn test_assert_node_aligned() {
+        assert_eq!(assert_node_aligned(&[]).expect("empty data is aligned"), 0);
+        assert_eq!(
+            assert_node_aligned(&[0u8; 4 * NODE_SIZE]).expect("aligned data rejected"),
+            4
+        );
+
+        for len in &[1, NODE_SIZE - 1, NODE_SIZE + 1, 4 * NODE_SIZE - 1] {
+            let err = assert_node_aligned(&vec![0u8; *len]).expect_err("unaligned data accepted");
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Data length {} is not a multiple of the node size {}",
+                    len, NODE_SIZE
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_data_at_node() {
+        let data: Vec<u8> = (0..3 * NODE_SIZE).map(|i| i as u8).collect();
+
+        for v in 0..3 {
+            assert_eq!(
+                data_at_node(&data, v).expect("data_at_node failure"),
+                &data[v * NODE_SIZE..(v + 1) * NODE_SIZE]
+            );
+        }
+
+        for v in &[3, usize::MAX / NODE_SIZE, usize::MAX] {
+            let err = data_at_node(&data, *v).expect_err("out of bounds node was read");
+            assert_eq!(
+                err.to_string(),
+                format!("Node {} is out of bounds for data of length 96", v)
+            );
+        }
+    }
+
     #[test]
     fn test_bytes_into_boolean_vec() {
         let mut cs = TestConstraintSystem::<Fr>::new();
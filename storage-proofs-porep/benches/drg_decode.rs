@@ -0,0 +1,49 @@
+use blstrs::Scalar as Fr;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use ff::Field;
+use filecoin_hashers::{sha256::Sha256Hasher, Domain, Hasher};
+use fr32::fr_into_bytes;
+use rand::thread_rng;
+use storage_proofs_core::{
+    api_version::ApiVersion,
+    drgraph::{BucketGraph, Graph, BASE_DEGREE},
+    util::NODE_SIZE,
+};
+use storage_proofs_porep::drg;
+
+fn drg_decode_benchmark(c: &mut Criterion) {
+    let nodes = 4096;
+    let mut rng = thread_rng();
+    let data: Vec<u8> = (0..nodes)
+        .flat_map(|_| fr_into_bytes(&Fr::random(&mut rng)))
+        .collect();
+    let replica_id = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    let graph =
+        BucketGraph::<Sha256Hasher>::new(nodes, BASE_DEGREE, 0, [32; 32], ApiVersion::V1_1_0)
+            .unwrap();
+
+    let mut group = c.benchmark_group("drg-decode");
+    group.throughput(Throughput::Bytes((nodes * NODE_SIZE) as u64));
+
+    group.bench_function("parallel-4096", |b| {
+        b.iter(|| {
+            let mut data = data.clone();
+            drg::decode(&graph, &replica_id, &mut data, None).unwrap();
+            black_box(data)
+        })
+    });
+
+    group.bench_function("sequential-4096", |b| {
+        b.iter(|| {
+            let blocks = (0..nodes)
+                .map(|node| drg::decode_block(&graph, &replica_id, &data, None, node).unwrap())
+                .collect::<Vec<_>>();
+            black_box(blocks)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, drg_decode_benchmark);
+criterion_main!(benches);
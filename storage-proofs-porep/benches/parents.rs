@@ -66,5 +66,32 @@ fn parents_loop_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, parents_loop_benchmark);
+/// Compares allocating a parents buffer per node, as `prove` used to per challenge, against reusing
+/// one buffer for every node.
+#[allow(clippy::unit_arg)]
+fn parents_buffer_benchmark(c: &mut Criterion) {
+    let sizes = vec![50, 1000];
+
+    let mut group = c.benchmark_group("parents buffer");
+    for size in sizes {
+        let graph = pregenerate_graph::<Sha256Hasher>(size, ApiVersion::V1_1_0);
+        group.bench_function(format!("allocating-{}", size), |b| {
+            b.iter(|| {
+                for node in 0..graph.size() {
+                    let mut parents = vec![0; graph.degree()];
+                    graph.parents(node, &mut parents).unwrap();
+                    black_box(parents);
+                }
+            })
+        });
+        group.bench_function(format!("reused-{}", size), |b| {
+            let mut parents = vec![0; graph.degree()];
+            b.iter(|| black_box(parents_loop::<Sha256Hasher, _>(&graph, &mut parents)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parents_loop_benchmark, parents_buffer_benchmark);
 criterion_main!(benches);
@@ -87,7 +87,7 @@ where
 
         let (comm_r, comm_d) = match pub_in.tau {
             None => (None, None),
-            Some(tau) => (Some(tau.comm_r), Some(tau.comm_d)),
+            Some(tau) => (Some(tau.comm_r.0), Some(tau.comm_d.0)),
         };
 
         let leaves = pub_params.graph.size();
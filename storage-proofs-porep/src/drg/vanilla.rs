@@ -1,10 +1,16 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{ensure, Context};
+use bincode::Options;
 use filecoin_hashers::{Domain, HashFunction, Hasher, PoseidonArity};
 use fr32::bytes_into_fr_repr_safe;
 use generic_array::typenum::U2;
+use log::trace;
 use merkletree::store::{ReplicaConfig, StoreConfig};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
@@ -13,28 +19,58 @@ use storage_proofs_core::{
     api_version::ApiVersion,
     cache_key::CacheKey,
     crypto::sloth,
-    drgraph::Graph,
+    drgraph::{graph_height, BucketGraph, Graph, GraphTopology, ParentMatrix},
     error::Result,
     merkle::{
         create_base_lcmerkle_tree, create_base_merkle_tree, BinaryLCMerkleTree, BinaryMerkleTree,
         LCMerkleTree, MerkleProof, MerkleProofTrait, MerkleTreeTrait,
     },
-    parameter_cache::ParameterSetMetadata,
+    parameter_cache::{versioned_identifier, ParameterSetMetadata},
     proof::{NoRequirements, ProofScheme},
-    util::{data_at_node, data_at_node_offset, NODE_SIZE},
+    util::{assert_node_aligned, data_at_node, data_at_node_offset, NODE_SIZE},
     Data,
 };
+#[cfg(feature = "arbitrary")]
+use storage_proofs_core::{
+    arbitrary::{self, Arbitrary, Unstructured},
+    merkle::arbitrary_domain,
+};
 
 use crate::{encode, PoRep};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Commitment to the original data: the root of the tree built over it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CommD<T>(pub T);
+
+/// Commitment to the replica: the root of the tree built over the encoded data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CommR<T>(pub T);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Tau<T> {
-    pub comm_r: T,
-    pub comm_d: T,
+    pub comm_r: CommR<T>,
+    pub comm_d: CommD<T>,
 }
 
 impl<T: Domain> Tau<T> {
-    pub fn new(comm_d: T, comm_r: T) -> Self {
+    /// ```
+    /// use filecoin_hashers::sha256::Sha256Domain;
+    /// use storage_proofs_porep::drg::{CommD, CommR, Tau};
+    ///
+    /// let tau = Tau::new(CommD(Sha256Domain::default()), CommR(Sha256Domain::default()));
+    /// ```
+    ///
+    /// Swapping the commitments does not compile:
+    ///
+    /// ```compile_fail
+    /// use filecoin_hashers::sha256::Sha256Domain;
+    /// use storage_proofs_porep::drg::{CommD, CommR, Tau};
+    ///
+    /// let tau = Tau::new(CommR(Sha256Domain::default()), CommD(Sha256Domain::default()));
+    /// ```
+    pub fn new(comm_d: CommD<T>, comm_r: CommR<T>) -> Self {
         Tau { comm_d, comm_r }
     }
 }
@@ -43,12 +79,64 @@ impl<T: Domain> Tau<T> {
 pub struct ProverAux<H: Hasher> {
     pub tree_d: BinaryMerkleTree<H>,
     pub tree_r: BinaryLCMerkleTree<H>,
+    /// The parents of every node, as used during replication. Only kept by
+    /// `DrgPoRep::replicate_with_parent_table`.
+    pub parent_table: Option<ParentMatrix>,
 }
 
 impl<H: Hasher> ProverAux<H> {
     pub fn new(tree_d: BinaryMerkleTree<H>, tree_r: BinaryLCMerkleTree<H>) -> Self {
-        ProverAux { tree_d, tree_r }
+        ProverAux {
+            tree_d,
+            tree_r,
+            parent_table: None,
+        }
     }
+
+    /// Keeps `parent_table` so it can be stored alongside the replica with `write_parent_table`.
+    pub fn with_parent_table(mut self, parent_table: ParentMatrix) -> Self {
+        self.parent_table = Some(parent_table);
+        self
+    }
+
+    /// Writes the parent table to `parent_table_path(replica_path)` and returns its commitment,
+    /// which verifiers need to load it again with `read_parent_table`.
+    pub fn write_parent_table(&self, replica_path: &Path) -> Result<[u8; 32]> {
+        let parent_table = self
+            .parent_table
+            .as_ref()
+            .context("no parent table was kept, replicate with replicate_with_parent_table")?;
+        let path = parent_table_path(replica_path);
+        let file = File::create(&path)
+            .with_context(|| format!("could not create parent table {:?}", path))?;
+        parent_table.write_to(BufWriter::new(file))?;
+
+        Ok(parent_table.commitment())
+    }
+}
+
+/// Returns the path the parent table of the replica at `replica_path` is stored at: the replica
+/// path with `.parents` appended.
+pub fn parent_table_path(replica_path: &Path) -> PathBuf {
+    let mut path = replica_path.as_os_str().to_owned();
+    path.push(".parents");
+    path.into()
+}
+
+/// Loads the parent table stored alongside the replica at `replica_path`, failing unless it
+/// matches `commitment`.
+pub fn read_parent_table(replica_path: &Path, commitment: &[u8; 32]) -> Result<ParentMatrix> {
+    let path = parent_table_path(replica_path);
+    let file =
+        File::open(&path).with_context(|| format!("could not open parent table {:?}", path))?;
+    let parent_table = ParentMatrix::read_from(BufReader::new(file))?;
+    ensure!(
+        &parent_table.commitment() == commitment,
+        "parent table {:?} does not match its commitment",
+        path
+    );
+
+    Ok(parent_table)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +176,63 @@ pub struct DrgParams {
     pub porep_id: [u8; 32],
 }
 
+impl DrgParams {
+    pub fn builder() -> DrgParamsBuilder {
+        DrgParamsBuilder::default()
+    }
+}
+
+/// Builds `DrgParams`, checking that the fields describe a valid graph.
+#[derive(Debug, Clone, Default)]
+pub struct DrgParamsBuilder {
+    nodes: usize,
+    degree: usize,
+    expansion_degree: usize,
+    porep_id: Option<[u8; 32]>,
+}
+
+impl DrgParamsBuilder {
+    pub fn nodes(mut self, nodes: usize) -> Self {
+        self.nodes = nodes;
+        self
+    }
+
+    pub fn degree(mut self, degree: usize) -> Self {
+        self.degree = degree;
+        self
+    }
+
+    /// Defaults to `0`.
+    pub fn expansion_degree(mut self, expansion_degree: usize) -> Self {
+        self.expansion_degree = expansion_degree;
+        self
+    }
+
+    /// Defaults to a random porep id.
+    pub fn porep_id(mut self, porep_id: [u8; 32]) -> Self {
+        self.porep_id = Some(porep_id);
+        self
+    }
+
+    pub fn build(self) -> Result<DrgParams> {
+        ensure!(self.nodes > 0, "nodes must not be zero");
+        ensure!(self.degree > 0, "degree must not be zero");
+        ensure!(
+            self.degree < self.nodes,
+            "degree {} must be less than the number of nodes {}",
+            self.degree,
+            self.nodes
+        );
+
+        Ok(DrgParams {
+            nodes: self.nodes,
+            degree: self.degree,
+            expansion_degree: self.expansion_degree,
+            porep_id: self.porep_id.unwrap_or_else(rand::random),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PublicParams<H, G>
 where
@@ -116,15 +261,32 @@ where
     }
 }
 
+impl<H: Hasher> PublicParams<H, BucketGraph<H>> {
+    /// Builds the public params needed to verify proofs from a serialized graph topology,
+    /// without going through `setup`.
+    pub fn for_verification(
+        topology: &GraphTopology,
+        private: bool,
+        challenges_count: usize,
+    ) -> Result<Self> {
+        let graph = BucketGraph::from_topology(topology)?;
+
+        Ok(PublicParams::new(graph, private, challenges_count))
+    }
+}
+
 impl<H, G> ParameterSetMetadata for PublicParams<H, G>
 where
     H: Hasher,
     G: Graph<H> + ParameterSetMetadata,
 {
     fn identifier(&self) -> String {
-        format!(
-            "drgporep::PublicParams{{graph: {}}}",
-            self.graph.identifier(),
+        versioned_identifier(
+            Self::schema_version(),
+            &format!(
+                "drgporep::PublicParams{{graph: {}}}",
+                self.graph.identifier(),
+            ),
         )
     }
 
@@ -133,7 +295,7 @@ where
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DataProof<H: Hasher, U: PoseidonArity> {
     #[serde(bound(
         serialize = "MerkleProof<H, U>: Serialize",
@@ -144,6 +306,8 @@ pub struct DataProof<H: Hasher, U: PoseidonArity> {
 }
 
 impl<H: Hasher, U: 'static + PoseidonArity> DataProof<H, U> {
+    /// Builds a placeholder opening of a tree of arity `U` with a path of height `n`, with the
+    /// same shape, and so serialized size, as an actual opening of such a tree.
     pub fn new(n: usize) -> Self {
         DataProof {
             proof: MerkleProof::new(n),
@@ -151,16 +315,39 @@ impl<H: Hasher, U: 'static + PoseidonArity> DataProof<H, U> {
         }
     }
 
+    /// Returns true if this is the placeholder built by `new`, i.e. it opens no actual data.
+    pub fn is_empty(&self) -> bool {
+        self.data == H::Domain::default()
+            && self.proof.leaf() == H::Domain::default()
+            && self.proof.root() == H::Domain::default()
+    }
+
     /// proves_challenge returns true if this self.proof corresponds to challenge.
     /// This is useful for verifying that a supplied proof is actually relevant to a given challenge.
     pub fn proves_challenge(&self, challenge: usize) -> bool {
         self.proof.proves_challenge(challenge)
     }
+
+    /// Writes the bincode encoding of this proof to `writer` without buffering it in memory.
+    pub fn serialize_into<W: Write>(&self, writer: W) -> Result<()> {
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, H: 'static + Hasher, U: 'static + PoseidonArity> Arbitrary<'a> for DataProof<H, U> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(DataProof {
+            proof: MerkleProof::arbitrary(u)?,
+            data: arbitrary_domain::<H>(u)?,
+        })
+    }
 }
 
 pub type ReplicaParents<H> = Vec<(u32, DataProof<H, U2>)>;
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Proof<H: Hasher> {
     #[serde(bound(
         serialize = "H::Domain: Serialize",
@@ -200,6 +387,20 @@ impl<H: Hasher> Proof<H> {
         }
     }
 
+    /// Returns true if this is the placeholder built by `new_empty`: default roots, only empty
+    /// openings and every parent set to node `0`. `verify` rejects such a proof.
+    pub fn is_empty(&self) -> bool {
+        self.data_root == H::Domain::default()
+            && self.replica_root == H::Domain::default()
+            && self.replica_nodes.iter().all(DataProof::is_empty)
+            && self.nodes.iter().all(DataProof::is_empty)
+            && self
+                .replica_parents
+                .iter()
+                .flatten()
+                .all(|(parent, data_proof)| *parent == 0 && data_proof.is_empty())
+    }
+
     pub fn new(
         replica_nodes: Vec<DataProof<H, U2>>,
         replica_parents: Vec<ReplicaParents<H>>,
@@ -213,6 +414,138 @@ impl<H: Hasher> Proof<H> {
             nodes,
         }
     }
+
+    /// Writes the bincode encoding of this proof to `writer` one challenge opening at a time,
+    /// so that the encoding of large proofs is never held in memory as a whole.
+    pub fn serialize_into<W: Write>(&self, mut writer: W) -> Result<()> {
+        bincode::serialize_into(&mut writer, &self.data_root)?;
+        bincode::serialize_into(&mut writer, &self.replica_root)?;
+
+        bincode::serialize_into(&mut writer, &(self.replica_nodes.len() as u64))?;
+        for replica_node in &self.replica_nodes {
+            replica_node.serialize_into(&mut writer)?;
+        }
+
+        bincode::serialize_into(&mut writer, &(self.replica_parents.len() as u64))?;
+        for parents in &self.replica_parents {
+            bincode::serialize_into(&mut writer, &(parents.len() as u64))?;
+            for (parent, data_proof) in parents {
+                bincode::serialize_into(&mut writer, parent)?;
+                data_proof.serialize_into(&mut writer)?;
+            }
+        }
+
+        bincode::serialize_into(&mut writer, &(self.nodes.len() as u64))?;
+        for node in &self.nodes {
+            node.serialize_into(&mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a proof in the layout written by `serialize_into`, for a graph with the given merkle
+    /// path `height` and `degree`, over `challenges` challenges. Never reads more than such a proof
+    /// takes, and fails if the input is truncated or does not have this shape.
+    pub fn deserialize_from<R: Read>(
+        mut reader: R,
+        height: usize,
+        degree: usize,
+        challenges: usize,
+    ) -> Result<Self> {
+        let data_proof_size = bincode::serialized_size(&DataProof::<H, U2>::new(height))?;
+        let read_data_proof = |reader: &mut R| -> Result<DataProof<H, U2>> {
+            let data_proof: DataProof<H, U2> = bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .allow_trailing_bytes()
+                .with_limit(data_proof_size)
+                .deserialize_from(reader)?;
+            ensure!(
+                data_proof.proof.path().len() == height,
+                "invalid merkle path length {}, expected {}",
+                data_proof.proof.path().len(),
+                height
+            );
+            Ok(data_proof)
+        };
+        let read_len = |reader: &mut R, expected: usize, what: &str| -> Result<()> {
+            let len: u64 = bincode::deserialize_from(reader)?;
+            ensure!(
+                len == expected as u64,
+                "invalid number of {} {}, expected {}",
+                what,
+                len,
+                expected
+            );
+            Ok(())
+        };
+
+        let data_root = bincode::deserialize_from(&mut reader)?;
+        let replica_root = bincode::deserialize_from(&mut reader)?;
+
+        read_len(&mut reader, challenges, "replica nodes")?;
+        let mut replica_nodes = Vec::with_capacity(challenges);
+        for _ in 0..challenges {
+            replica_nodes.push(read_data_proof(&mut reader)?);
+        }
+
+        read_len(&mut reader, challenges, "replica parent sets")?;
+        let mut replica_parents = Vec::with_capacity(challenges);
+        for _ in 0..challenges {
+            read_len(&mut reader, degree, "replica parents")?;
+            let mut parents = Vec::with_capacity(degree);
+            for _ in 0..degree {
+                let parent: u32 = bincode::deserialize_from(&mut reader)?;
+                parents.push((parent, read_data_proof(&mut reader)?));
+            }
+            replica_parents.push(parents);
+        }
+
+        read_len(&mut reader, challenges, "nodes")?;
+        let mut nodes = Vec::with_capacity(challenges);
+        for _ in 0..challenges {
+            nodes.push(read_data_proof(&mut reader)?);
+        }
+
+        Ok(Proof {
+            data_root,
+            replica_root,
+            replica_nodes,
+            replica_parents,
+            nodes,
+        })
+    }
+}
+
+/// Builds proofs whose shape is valid, but whose contents are arbitrary, for fuzzing `verify`.
+#[cfg(feature = "arbitrary")]
+impl<'a, H: 'static + Hasher> Arbitrary<'a> for Proof<H> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let challenges: usize = u.int_in_range(0..=4)?;
+
+        let mut replica_nodes = Vec::with_capacity(challenges);
+        let mut replica_parents = Vec::with_capacity(challenges);
+        let mut nodes = Vec::with_capacity(challenges);
+        for _ in 0..challenges {
+            replica_nodes.push(DataProof::arbitrary(u)?);
+
+            let degree: usize = u.int_in_range(0..=16)?;
+            let mut parents = Vec::with_capacity(degree);
+            for _ in 0..degree {
+                parents.push((u.arbitrary()?, DataProof::arbitrary(u)?));
+            }
+            replica_parents.push(parents);
+
+            nodes.push(DataProof::arbitrary(u)?);
+        }
+
+        Ok(Proof {
+            data_root: arbitrary_domain::<H>(u)?,
+            replica_root: arbitrary_domain::<H>(u)?,
+            replica_nodes,
+            replica_parents,
+            nodes,
+        })
+    }
 }
 
 impl<'a, H: Hasher> From<&'a Proof<H>> for Proof<H> {
@@ -227,6 +560,273 @@ impl<'a, H: Hasher> From<&'a Proof<H>> for Proof<H> {
     }
 }
 
+/// A `Proof` in which every distinct parent of a challenge carries its inclusion proof only once.
+/// Graphs with repeated parents produce considerably smaller serialized proofs in this form.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupProof<H: Hasher> {
+    #[serde(bound(
+        serialize = "H::Domain: Serialize",
+        deserialize = "H::Domain: Deserialize<'de>"
+    ))]
+    pub data_root: H::Domain,
+    #[serde(bound(
+        serialize = "H::Domain: Serialize",
+        deserialize = "H::Domain: Deserialize<'de>"
+    ))]
+    pub replica_root: H::Domain,
+    #[serde(bound(
+        serialize = "DataProof<H, U2>: Serialize",
+        deserialize = "DataProof<H, U2>: Deserialize<'de>"
+    ))]
+    pub replica_nodes: Vec<DataProof<H, U2>>,
+    /// For each challenge, the full list of parents, each paired with the index of its proof in
+    /// the corresponding entry of `parent_proofs`. The index is below the degree of the graph,
+    /// and fixed size so the serialized proof does not depend on the platform.
+    pub replica_parents: Vec<Vec<(u32, u32)>>,
+    /// For each challenge, the inclusion proofs of its distinct parents.
+    #[serde(bound(
+        serialize = "DataProof<H, U2>: Serialize",
+        deserialize = "DataProof<H, U2>: Deserialize<'de>"
+    ))]
+    pub parent_proofs: Vec<Vec<DataProof<H, U2>>>,
+    #[serde(bound(
+        serialize = "DataProof<H, U2>: Serialize",
+        deserialize = "DataProof<H, U2>: Deserialize<'de>"
+    ))]
+    pub nodes: Vec<DataProof<H, U2>>,
+}
+
+impl<H: Hasher> DedupProof<H> {
+    /// Reconstructs the full `Proof`, with one parent proof per entry of the parent list.
+    pub fn expand(&self) -> Result<Proof<H>> {
+        ensure!(
+            self.replica_parents.len() == self.parent_proofs.len(),
+            "mismatched parent lists: {} != {}",
+            self.replica_parents.len(),
+            self.parent_proofs.len()
+        );
+
+        let replica_parents = self
+            .replica_parents
+            .iter()
+            .zip(&self.parent_proofs)
+            .map(|(parents, proofs)| {
+                parents
+                    .iter()
+                    .map(|(parent, index)| {
+                        let proof = proofs.get(*index as usize).with_context(|| {
+                            format!("invalid proof index {} for parent {}", index, parent)
+                        })?;
+                        Ok((*parent, proof.clone()))
+                    })
+                    .collect::<Result<ReplicaParents<H>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Proof {
+            data_root: self.data_root,
+            replica_root: self.replica_root,
+            replica_nodes: self.replica_nodes.clone(),
+            replica_parents,
+            nodes: self.nodes.clone(),
+        })
+    }
+}
+
+impl<H: Hasher> From<Proof<H>> for DedupProof<H> {
+    fn from(proof: Proof<H>) -> Self {
+        let mut replica_parents = Vec::with_capacity(proof.replica_parents.len());
+        let mut parent_proofs = Vec::with_capacity(proof.replica_parents.len());
+
+        for parents in proof.replica_parents {
+            let mut seen: Vec<u32> = Vec::with_capacity(parents.len());
+            let mut indices = Vec::with_capacity(parents.len());
+            let mut proofs = Vec::with_capacity(parents.len());
+
+            for (parent, data_proof) in parents {
+                let index = match seen.iter().position(|p| *p == parent) {
+                    Some(index) => index,
+                    None => {
+                        seen.push(parent);
+                        proofs.push(data_proof);
+                        proofs.len() - 1
+                    }
+                };
+                indices.push((parent, index as u32));
+            }
+
+            replica_parents.push(indices);
+            parent_proofs.push(proofs);
+        }
+
+        DedupProof {
+            data_root: proof.data_root,
+            replica_root: proof.replica_root,
+            replica_nodes: proof.replica_nodes,
+            replica_parents,
+            parent_proofs,
+            nodes: proof.nodes,
+        }
+    }
+}
+
+/// A collection of `Proof`s, e.g. for many sectors, together with the topology of the graph
+/// each was generated over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateProof<H: Hasher> {
+    #[serde(bound(
+        serialize = "Proof<H>: Serialize",
+        deserialize = "Proof<H>: Deserialize<'de>"
+    ))]
+    pub proofs: Vec<Proof<H>>,
+    pub metadata: Vec<GraphTopology>,
+}
+
+impl<H: 'static + Hasher> AggregateProof<H> {
+    pub fn new(proofs: Vec<Proof<H>>, metadata: Vec<GraphTopology>) -> Self {
+        AggregateProof { proofs, metadata }
+    }
+
+    /// Verifies every contained proof against the matching public params and inputs.
+    /// Returns the index of the first proof which fails, or `None` if all of them verify.
+    pub fn verify_all(
+        &self,
+        pub_params_list: &[PublicParams<H, BucketGraph<H>>],
+        pub_inputs_list: &[PublicInputs<H::Domain>],
+    ) -> Result<Option<usize>> {
+        ensure!(
+            self.metadata.len() == self.proofs.len(),
+            "aggregate proof has {} proofs but {} topologies",
+            self.proofs.len(),
+            self.metadata.len()
+        );
+        ensure!(
+            pub_params_list.len() == self.proofs.len()
+                && pub_inputs_list.len() == self.proofs.len(),
+            "aggregate proof has {} proofs but {} public params and {} public inputs were given",
+            self.proofs.len(),
+            pub_params_list.len(),
+            pub_inputs_list.len()
+        );
+
+        for (i, ((proof, topology), (pub_params, pub_inputs))) in self
+            .proofs
+            .iter()
+            .zip(&self.metadata)
+            .zip(pub_params_list.iter().zip(pub_inputs_list))
+            .enumerate()
+        {
+            if *topology != pub_params.graph.topology()
+                || !DrgPoRep::<H, BucketGraph<H>>::verify(pub_params, pub_inputs, proof)
+                    .with_context(|| format!("proof {} failed", i))?
+            {
+                return Ok(Some(i));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Proves many replicas laid out over the same graph, e.g. sectors sharing their DRG parameters,
+/// against one set of public params. The parents of each challenged node are computed once and
+/// reused for every replica challenged at that node.
+#[derive(Debug)]
+pub struct MultiReplicaProver<'a, H, G>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    pub_params: &'a PublicParams<H, G>,
+    parents: HashMap<usize, Vec<u32>>,
+}
+
+impl<'a, H, G> MultiReplicaProver<'a, H, G>
+where
+    H: 'static + Hasher,
+    G: 'a + Graph<H> + ParameterSetMetadata,
+{
+    pub fn new(pub_params: &'a PublicParams<H, G>) -> Self {
+        MultiReplicaProver {
+            pub_params,
+            parents: HashMap::new(),
+        }
+    }
+
+    pub fn pub_params(&self) -> &'a PublicParams<H, G> {
+        self.pub_params
+    }
+
+    /// Returns the number of nodes whose parents have been computed so far.
+    pub fn cached_parents(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Proves `challenges` for each `(replica_id, priv_inputs)` in `replicas`, returning one proof
+    /// per replica, in order.
+    pub fn prove_all(
+        &mut self,
+        challenges: &[usize],
+        replicas: &[(H::Domain, PrivateInputs<'_, H>)],
+    ) -> Result<Vec<Proof<H>>> {
+        let pub_params = self.pub_params;
+        let cache = &mut self.parents;
+
+        replicas
+            .iter()
+            .map(|(replica_id, priv_inputs)| {
+                let pub_inputs = PublicInputs {
+                    replica_id: Some(*replica_id),
+                    challenges: challenges.to_vec(),
+                    tau: None,
+                };
+                DrgPoRep::<H, G>::prove_with_parents(
+                    pub_params,
+                    &pub_inputs,
+                    priv_inputs,
+                    |node, parents| {
+                        match cache.entry(node) {
+                            Entry::Occupied(entry) => parents.clone_from(entry.get()),
+                            Entry::Vacant(entry) => {
+                                pub_params.graph.parents_checked(node, parents)?;
+                                entry.insert(parents.clone());
+                            }
+                        }
+                        Ok(())
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Statistics about a single verification, as returned by `DrgPoRep::verify_reported`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub total_challenges: usize,
+    /// The number of merkle proofs validated before verification completed or failed.
+    pub merkle_validations: usize,
+    pub elapsed: Duration,
+    /// The size of the proof, serialized with bincode.
+    pub proof_bytes: usize,
+}
+
+/// The work a call to `DrgPoRep::prove` performs, as estimated by `DrgPoRep::plan_prove`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvePlan {
+    pub challenges: usize,
+    /// The number of inclusion proofs in the resulting proof.
+    pub merkle_proofs: usize,
+    /// The number of inclusion proofs generated from the trees. Repeated parents share a proof.
+    pub gen_proof_calls: usize,
+    /// The number of parent nodes read from the replica tree, for inclusion proofs and for
+    /// deriving the decoding keys.
+    pub parent_reads: usize,
+    pub block_decodes: usize,
+    /// The size of the resulting proof, serialized with bincode.
+    pub proof_bytes: usize,
+}
+
 #[derive(Default)]
 pub struct DrgPoRep<'a, H, G>
 where
@@ -266,21 +866,64 @@ where
         pub_inputs: &'b Self::PublicInputs,
         priv_inputs: &'b Self::PrivateInputs,
     ) -> Result<Self::Proof> {
+        Self::prove_with_parents(pub_params, pub_inputs, priv_inputs, |node, parents| {
+            pub_params.graph.parents_checked(node, parents)
+        })
+    }
+
+    fn verify(
+        pub_params: &Self::PublicParams,
+        pub_inputs: &Self::PublicInputs,
+        proof: &Self::Proof,
+    ) -> Result<bool> {
+        Self::verify_inner(pub_params, pub_inputs, proof, None, true, &mut 0, None)
+            .map(|valid| valid == Some(true))
+    }
+}
+
+impl<'a, H, G> DrgPoRep<'a, H, G>
+where
+    H: 'static + Hasher,
+    G: 'a + Graph<H> + ParameterSetMetadata,
+{
+    /// The implementation of `prove`, looking up the parents of each challenged node with
+    /// `parents_of`, which must fill the buffer exactly like `Graph::parents_checked`.
+    fn prove_with_parents<F>(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        priv_inputs: &PrivateInputs<'_, H>,
+        mut parents_of: F,
+    ) -> Result<Proof<H>>
+    where
+        F: FnMut(usize, &mut Vec<u32>) -> Result<()>,
+    {
         let len = pub_inputs.challenges.len();
         ensure!(
             len <= pub_params.challenges_count,
-            "too many challenges {} > {}",
+            "too many challenges: {} supplied, but at most {} are allowed",
             len,
             pub_params.challenges_count
         );
+        ensure!(pub_params.graph.size() > 0, "cannot prove an empty graph");
+        let replica_id = pub_inputs
+            .replica_id
+            .context("replica_id is required to prove")?;
 
         let mut replica_nodes = Vec::with_capacity(len);
         let mut replica_parents = Vec::with_capacity(len);
         let mut data_nodes: Vec<DataProof<H, U2>> = Vec::with_capacity(len);
+        // `parents_of` resizes the buffer and overwrites every entry, so one buffer serves all
+        // challenges, both to open the parents and to decode the challenged node.
+        let mut parents = Vec::with_capacity(pub_params.graph.degree());
 
         for i in 0..len {
             let challenge = pub_inputs.challenges[i] % pub_params.graph.size();
-            ensure!(challenge != 0, "cannot prove the first node");
+            ensure!(
+                pub_params.graph.degree_for_node(challenge) > 0,
+                "challenge {} maps to node {}, which has no parents and cannot be challenged",
+                pub_inputs.challenges[i],
+                challenge
+            );
 
             let tree_d = &priv_inputs.tree_d;
             let tree_r = &priv_inputs.tree_r;
@@ -294,19 +937,23 @@ where
                 data,
             });
 
-            let mut parents = vec![0; pub_params.graph.degree()];
-            pub_params.graph.parents(challenge, &mut parents)?;
-            let mut replica_parentsi = Vec::with_capacity(parents.len());
-
-            for p in &parents {
-                replica_parentsi.push((*p, {
-                    let proof = tree_r
-                        .gen_cached_proof(*p as usize, Some(tree_r_config_rows_to_discard))?;
-                    DataProof {
-                        proof,
-                        data: tree_r.read_at(*p as usize)?,
+            parents_of(challenge, &mut parents)?;
+            let mut replica_parentsi: ReplicaParents<H> = Vec::with_capacity(parents.len());
+
+            for (j, p) in parents.iter().enumerate() {
+                // Repeated parents share the inclusion proof generated for their first occurrence.
+                let data_proof = match parents[..j].iter().position(|q| q == p) {
+                    Some(k) => replica_parentsi[k].1.clone(),
+                    None => {
+                        let proof = tree_r
+                            .gen_cached_proof(*p as usize, Some(tree_r_config_rows_to_discard))?;
+                        DataProof {
+                            proof,
+                            data: tree_r.read_at(*p as usize)?,
+                        }
                     }
-                }));
+                };
+                replica_parentsi.push((*p, data_proof));
             }
 
             replica_parents.push(replica_parentsi);
@@ -323,7 +970,7 @@ where
                 // )?;
 
                 let extracted = decode_domain_block::<H>(
-                    &pub_inputs.replica_id.context("missing replica_id")?,
+                    &replica_id,
                     tree_r,
                     challenge,
                     tree_r.read_at(challenge)?,
@@ -341,39 +988,284 @@ where
         Ok(proof)
     }
 
-    fn verify(
-        pub_params: &Self::PublicParams,
-        pub_inputs: &Self::PublicInputs,
-        proof: &Self::Proof,
+    /// Like `prove`, but returns a proof storing each distinct parent's inclusion proof once.
+    pub fn prove_dedup(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        priv_inputs: &PrivateInputs<'a, H>,
+    ) -> Result<DedupProof<H>> {
+        Self::prove(pub_params, pub_inputs, priv_inputs).map(Into::into)
+    }
+
+    /// Computes the work `prove` would perform for `pub_inputs`, without accessing any trees.
+    pub fn plan_prove(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+    ) -> Result<ProvePlan> {
+        let len = pub_inputs.challenges.len();
+        ensure!(
+            len <= pub_params.challenges_count,
+            "too many challenges: {} supplied, but at most {} are allowed",
+            len,
+            pub_params.challenges_count
+        );
+        ensure!(pub_params.graph.size() > 0, "cannot prove an empty graph");
+
+        let degree = pub_params.graph.degree();
+        let mut parents = vec![0; degree];
+        let mut unique_parents = 0;
+        for requested in &pub_inputs.challenges {
+            let challenge = requested % pub_params.graph.size();
+            ensure!(
+                pub_params.graph.degree_for_node(challenge) > 0,
+                "challenge {} maps to node {}, which has no parents and cannot be challenged",
+                requested,
+                challenge
+            );
+
+            pub_params.graph.parents(challenge, &mut parents)?;
+            unique_parents += parents
+                .iter()
+                .enumerate()
+                .filter(|(j, p)| !parents[..*j].contains(p))
+                .count();
+        }
+
+        // Every inclusion proof has the same shape, so an empty proof has the size of a real one.
+        let height = graph_height::<U2>(pub_params.graph.size()) - 1;
+        let data_proof = DataProof::<H, U2> {
+            proof: MerkleProof::from_path(
+                vec![(vec![Default::default()], 0); height],
+                Default::default(),
+                Default::default(),
+            ),
+            data: Default::default(),
+        };
+        let shape = Proof::<H> {
+            data_root: Default::default(),
+            replica_root: Default::default(),
+            replica_nodes: vec![data_proof.clone(); len],
+            replica_parents: vec![vec![(0, data_proof.clone()); degree]; len],
+            nodes: vec![data_proof; len],
+        };
+
+        Ok(ProvePlan {
+            challenges: len,
+            merkle_proofs: len * (degree + 2),
+            gen_proof_calls: 2 * len + unique_parents,
+            parent_reads: unique_parents + len * degree,
+            block_decodes: len,
+            proof_bytes: bincode::serialized_size(&shape)? as usize,
+        })
+    }
+
+    /// Like `verify`, but gives up once `deadline` has passed, checked before each challenge.
+    /// Returns `None` if verification timed out.
+    pub fn verify_with_deadline(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        proof: &Proof<H>,
+        deadline: Instant,
+    ) -> Result<Option<bool>> {
+        Self::verify_inner(
+            pub_params,
+            pub_inputs,
+            proof,
+            None,
+            true,
+            &mut 0,
+            Some(deadline),
+        )
+    }
+
+    /// Like `verify`, but additionally reports how much work verification took.
+    pub fn verify_reported(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        proof: &Proof<H>,
+    ) -> Result<(bool, VerifyReport)> {
+        let start = Instant::now();
+        let mut merkle_validations = 0;
+        let valid = Self::verify_inner(
+            pub_params,
+            pub_inputs,
+            proof,
+            None,
+            true,
+            &mut merkle_validations,
+            None,
+        )? == Some(true);
+        let elapsed = start.elapsed();
+
+        let report = VerifyReport {
+            total_challenges: pub_inputs.challenges.len(),
+            merkle_validations,
+            elapsed,
+            proof_bytes: bincode::serialized_size(proof)? as usize,
+        };
+
+        Ok((valid, report))
+    }
+
+    /// Like `verify`, but additionally returns every challenge along with the bytes of the
+    /// unsealed node it was decoded to, if the proof is valid. Verification checks that the
+    /// decoded node equals the data node opened by the proof, so these are the bytes of that
+    /// opening. If the proof is invalid, no nodes are returned.
+    pub fn verify_and_extract(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        proof: &Proof<H>,
+    ) -> Result<(bool, Vec<(usize, Vec<u8>)>)> {
+        if !Self::verify(pub_params, pub_inputs, proof)? {
+            return Ok((false, Vec::new()));
+        }
+
+        let unsealed = pub_inputs
+            .challenges
+            .iter()
+            .zip(&proof.nodes)
+            .map(|(challenge, node)| (*challenge, node.data.into_bytes()))
+            .collect();
+
+        Ok((true, unsealed))
+    }
+
+    /// Decodes `node` of the replica `data` and returns it as a domain element, rather than
+    /// writing its bytes back into `data` like `extract`.
+    pub fn extract_domain(
+        pub_params: &PublicParams<H, G>,
+        replica_id: &H::Domain,
+        data: &[u8],
+        node: usize,
+    ) -> Result<H::Domain>
+    where
+        G::Key: AsRef<H::Domain>,
+    {
+        decode_block(&pub_params.graph, replica_id, data, None, node)
+    }
+
+    /// Checks only that `proof` is well-formed for `pub_inputs`: that it opens the challenged
+    /// nodes, that their parents are the ones the graph prescribes, and that every merkle path is
+    /// valid. Unlike `verify`, it does not decode the replica nodes, so it does NOT check that the
+    /// replica encodes the committed data; a proof passing it is not necessarily valid.
+    pub fn verify_structure_only(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        proof: &Proof<H>,
     ) -> Result<bool> {
+        Self::verify_inner(pub_params, pub_inputs, proof, None, false, &mut 0, None)
+            .map(|valid| valid == Some(true))
+    }
+
+    /// Like `verify`, but takes the expected parents of every challenge from `parent_table`
+    /// instead of generating them with the graph, e.g. a table loaded with `read_parent_table`.
+    pub fn verify_with_parent_table(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        proof: &Proof<H>,
+        parent_table: &ParentMatrix,
+    ) -> Result<bool> {
+        ensure!(
+            parent_table.len() == pub_params.graph.size()
+                && parent_table.degree() == pub_params.graph.degree(),
+            "parent table of {} nodes of degree {} does not fit a graph of {} nodes of degree {}",
+            parent_table.len(),
+            parent_table.degree(),
+            pub_params.graph.size(),
+            pub_params.graph.degree()
+        );
+
+        Self::verify_inner(
+            pub_params,
+            pub_inputs,
+            proof,
+            Some(parent_table),
+            true,
+            &mut 0,
+            None,
+        )
+        .map(|valid| valid == Some(true))
+    }
+
+    /// The implementation of `verify`, counting every merkle proof validated in
+    /// `merkle_validations`. Returns `None` if `deadline` passes before all challenges were
+    /// verified. The expected parents are taken from `parent_table` if given, and generated with
+    /// the graph otherwise. Unless `check_data` is set, replica nodes are not decoded and checked
+    /// against the data nodes (see `verify_structure_only`).
+    fn verify_inner(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        proof: &Proof<H>,
+        parent_table: Option<&ParentMatrix>,
+        check_data: bool,
+        merkle_validations: &mut usize,
+        deadline: Option<Instant>,
+    ) -> Result<Option<bool>> {
+        let replica_id = if check_data {
+            Some(
+                pub_inputs
+                    .replica_id
+                    .context("replica_id is required to verify")?,
+            )
+        } else {
+            None
+        };
+        ensure!(
+            !proof.is_empty(),
+            "cannot verify an empty placeholder proof"
+        );
         let mut hasher = Sha256::new();
 
-        for i in 0..pub_inputs.challenges.len() {
+        let len = pub_inputs.challenges.len();
+        if proof.nodes.len() != len
+            || proof.replica_nodes.len() != len
+            || proof.replica_parents.len() != len
+        {
+            trace!("proof does not contain exactly one opening per challenge");
+            return Ok(Some(false));
+        }
+
+        if let Some(tau) = &pub_inputs.tau {
+            if proof.data_root != tau.comm_d.0 || proof.replica_root != tau.comm_r.0 {
+                trace!("proof roots do not match the committed tau");
+                return Ok(Some(false));
+            }
+        }
+
+        for i in 0..len {
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+
             {
                 // This was verify_proof_meta.
                 if pub_inputs.challenges[i] >= pub_params.graph.size() {
-                    return Ok(false);
+                    return Ok(Some(false));
                 }
 
                 if !(proof.nodes[i].proves_challenge(pub_inputs.challenges[i])) {
-                    return Ok(false);
+                    return Ok(Some(false));
                 }
 
                 if !(proof.replica_nodes[i].proves_challenge(pub_inputs.challenges[i])) {
-                    return Ok(false);
+                    return Ok(Some(false));
                 }
 
-                let mut expected_parents = vec![0; pub_params.graph.degree()];
-                pub_params
-                    .graph
-                    .parents(pub_inputs.challenges[i], &mut expected_parents)?;
+                let mut expected_parents = Vec::with_capacity(pub_params.graph.degree());
+                match parent_table {
+                    Some(parent_table) => expected_parents
+                        .extend_from_slice(parent_table.row(pub_inputs.challenges[i])),
+                    None => pub_params
+                        .graph
+                        .parents_checked(pub_inputs.challenges[i], &mut expected_parents)?,
+                }
                 if proof.replica_parents[i].len() != expected_parents.len() {
                     println!(
                         "proof parents were not the same length as in public parameters: {} != {}",
                         proof.replica_parents[i].len(),
                         expected_parents.len()
                     );
-                    return Ok(false);
+                    return Ok(Some(false));
                 }
 
                 let parents_as_expected = proof.replica_parents[i]
@@ -383,26 +1275,43 @@ where
 
                 if !parents_as_expected {
                     println!("proof parents were not those provided in public parameters");
-                    return Ok(false);
+                    return Ok(Some(false));
                 }
             }
 
             let challenge = pub_inputs.challenges[i] % pub_params.graph.size();
-            ensure!(challenge != 0, "cannot prove the first node");
-
+            ensure!(
+                pub_params.graph.degree_for_node(challenge) > 0,
+                "challenge {} maps to node {}, which has no parents and cannot be challenged",
+                pub_inputs.challenges[i],
+                challenge
+            );
+
+            *merkle_validations += 1;
             if !proof.replica_nodes[i].proof.validate(challenge) {
-                return Ok(false);
+                return Ok(Some(false));
             }
 
             for (parent_node, p) in &proof.replica_parents[i] {
+                *merkle_validations += 1;
                 if !p.proof.validate(*parent_node as usize) {
-                    return Ok(false);
+                    return Ok(Some(false));
                 }
             }
 
+            let replica_id = match replica_id {
+                Some(replica_id) => replica_id,
+                None => {
+                    *merkle_validations += 1;
+                    if !proof.nodes[i].proof.validate(challenge) {
+                        return Ok(Some(false));
+                    }
+                    continue;
+                }
+            };
+
             let key = {
-                let prover_bytes = pub_inputs.replica_id.context("missing replica_id")?;
-                hasher.update(AsRef::<[u8]>::as_ref(&prover_bytes));
+                hasher.update(AsRef::<[u8]>::as_ref(&replica_id));
 
                 for p in proof.replica_parents[i].iter() {
                     hasher.update(AsRef::<[u8]>::as_ref(&p.1.data));
@@ -415,16 +1324,30 @@ where
             let unsealed = encode::decode(key, proof.replica_nodes[i].data);
 
             if unsealed != proof.nodes[i].data {
-                return Ok(false);
+                return Ok(Some(false));
             }
 
+            *merkle_validations += 1;
             if !proof.nodes[i].proof.validate_data(unsealed) {
                 println!("invalid data for merkle path {:?}", unsealed);
-                return Ok(false);
+                return Ok(Some(false));
             }
         }
 
-        Ok(true)
+        Ok(Some(true))
+    }
+
+    /// Verifies a proof produced by `prove_dedup`. A proof referencing missing parent proofs is
+    /// invalid.
+    pub fn verify_dedup(
+        pub_params: &PublicParams<H, G>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        proof: &DedupProof<H>,
+    ) -> Result<bool> {
+        match proof.expand() {
+            Ok(proof) => Self::verify(pub_params, pub_inputs, &proof),
+            Err(_) => Ok(false),
+        }
     }
 }
 
@@ -440,11 +1363,85 @@ where
     fn replicate(
         pp: &Self::PublicParams,
         replica_id: &<H as Hasher>::Domain,
-        mut data: Data<'a>,
+        data: Data<'a>,
         data_tree: Option<BinaryMerkleTree<H>>,
         config: StoreConfig,
         replica_path: PathBuf,
     ) -> Result<(Self::Tau, Self::ProverAux)> {
+        Self::replicate_inner(pp, replica_id, data, data_tree, config, replica_path, false)
+    }
+
+    fn extract_all<'b>(
+        pp: &'b Self::PublicParams,
+        replica_id: &'b <H as Hasher>::Domain,
+        data: &'b mut [u8],
+        _config: Option<StoreConfig>,
+    ) -> Result<()> {
+        assert_node_aligned(data)?;
+        ensure!(
+            data.len() == pp.graph.data_size(),
+            "data is {} bytes, but the graph covers {}",
+            data.len(),
+            pp.graph.data_size()
+        );
+
+        decode(&pp.graph, replica_id, data, None)
+    }
+
+    fn extract(
+        pp: &Self::PublicParams,
+        replica_id: &<H as Hasher>::Domain,
+        data: &mut [u8],
+        node: usize,
+        _config: Option<StoreConfig>,
+    ) -> Result<()> {
+        let block = Self::extract_domain(pp, replica_id, data, node)?;
+        let start = node * NODE_SIZE;
+        let end = start + NODE_SIZE;
+        let dest = &mut data[start..end];
+        dest.copy_from_slice(AsRef::<[u8]>::as_ref(&block));
+
+        Ok(())
+    }
+}
+
+impl<'a, H, G> DrgPoRep<'a, H, G>
+where
+    H: 'static + Hasher,
+    G::Key: AsRef<<H as Hasher>::Domain>,
+    G: 'a + Graph<H> + ParameterSetMetadata + Sync + Send,
+{
+    /// Like `replicate`, but keeps the parent table computed during replication in the returned
+    /// `ProverAux`, so that it can be stored alongside the replica with `write_parent_table`.
+    /// The table holds `degree()` parents for every node until the `ProverAux` is dropped.
+    pub fn replicate_with_parent_table(
+        pp: &PublicParams<H, G>,
+        replica_id: &H::Domain,
+        data: Data<'a>,
+        data_tree: Option<BinaryMerkleTree<H>>,
+        config: StoreConfig,
+        replica_path: PathBuf,
+    ) -> Result<(Tau<H::Domain>, ProverAux<H>)> {
+        Self::replicate_inner(pp, replica_id, data, data_tree, config, replica_path, true)
+    }
+
+    fn replicate_inner(
+        pp: &PublicParams<H, G>,
+        replica_id: &H::Domain,
+        mut data: Data<'a>,
+        data_tree: Option<BinaryMerkleTree<H>>,
+        config: StoreConfig,
+        replica_path: PathBuf,
+        keep_parent_table: bool,
+    ) -> Result<(Tau<H::Domain>, ProverAux<H>)> {
+        assert_node_aligned(data.as_ref())?;
+        ensure!(
+            data.as_ref().len() == pp.graph.data_size(),
+            "data is {} bytes, but the graph covers {}",
+            data.as_ref().len(),
+            pp.graph.data_size()
+        );
+
         let tree_d = match data_tree {
             Some(tree) => tree,
             None => create_base_merkle_tree::<BinaryMerkleTree<H>>(
@@ -462,16 +1459,15 @@ where
         // we can always get each parent's encodings with a simple lookup --
         // since we will already have encoded the parent earlier in the traversal.
 
-        let mut parents = vec![0; graph.degree()];
+        let parents = graph.parents_all()?;
         for node in 0..graph.size() {
-            graph.parents(node, &mut parents)?;
-            let key = graph.create_key(replica_id, node, &parents, data.as_ref(), None)?;
-            let start = data_at_node_offset(node);
-            let end = start + NODE_SIZE;
-
-            let node_data = <H as Hasher>::Domain::try_from_bytes(&data.as_ref()[start..end])?;
+            let key = graph.create_key(replica_id, node, parents.row(node), data.as_ref(), None)?;
+            let node_data =
+                <H as Hasher>::Domain::try_from_bytes(data_at_node(data.as_ref(), node)?)?;
             let encoded: H::Domain = sloth_encode::<H>(key.as_ref(), &node_data);
 
+            let start = data_at_node_offset(node);
+            let end = start + NODE_SIZE;
             encoded.write_bytes(&mut data.as_mut()[start..end])?;
         }
 
@@ -489,35 +1485,15 @@ where
                 &replica_config,
             )?;
 
-        let comm_d = tree_d.root();
-        let comm_r = tree_r.root();
-
-        Ok((Tau::new(comm_d, comm_r), ProverAux::new(tree_d, tree_r)))
-    }
+        let comm_d = CommD(tree_d.root());
+        let comm_r = CommR(tree_r.root());
 
-    fn extract_all<'b>(
-        pp: &'b Self::PublicParams,
-        replica_id: &'b <H as Hasher>::Domain,
-        data: &'b mut [u8],
-        _config: Option<StoreConfig>,
-    ) -> Result<()> {
-        decode(&pp.graph, replica_id, data, None)
-    }
-
-    fn extract(
-        pp: &Self::PublicParams,
-        replica_id: &<H as Hasher>::Domain,
-        data: &mut [u8],
-        node: usize,
-        _config: Option<StoreConfig>,
-    ) -> Result<()> {
-        let block = decode_block(&pp.graph, replica_id, data, None, node)?;
-        let start = node * NODE_SIZE;
-        let end = start + NODE_SIZE;
-        let dest = &mut data[start..end];
-        dest.copy_from_slice(AsRef::<[u8]>::as_ref(&block));
+        let mut aux = ProverAux::new(tree_d, tree_r);
+        if keep_parent_table {
+            aux = aux.with_parent_table(parents);
+        }
 
-        Ok(())
+        Ok((Tau::new(comm_d, comm_r), aux))
     }
 }
 
@@ -532,17 +1508,15 @@ where
     G::Key: AsRef<H::Domain>,
     G: Graph<H> + Sync,
 {
-    // TODO: proper error handling
-    let result: Vec<u8> = (0..graph.size())
+    let blocks = (0..graph.size())
         .into_par_iter()
-        .flat_map(|i| {
-            decode_block::<H, G>(graph, replica_id, data, exp_parents_data, i)
-                .expect("decode block failure")
-                .into_bytes()
-        })
-        .collect();
+        .map(|i| decode_block::<H, G>(graph, replica_id, data, exp_parents_data, i))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (dest, block) in data.chunks_mut(NODE_SIZE).zip(blocks) {
+        dest.copy_from_slice(AsRef::<[u8]>::as_ref(&block));
+    }
 
-    data.copy_from_slice(&result);
     Ok(())
 }
 
@@ -614,6 +1588,28 @@ pub fn replica_id<H: Hasher>(prover_id: [u8; 32], sector_id: [u8; 32]) -> H::Dom
     H::Function::hash_leaf(&to_hash)
 }
 
+/// Domain separation tag for `derive_replica_id`.
+const REPLICA_ID_DST: &[u8] = b"Filecoin_DrgReplicaID";
+
+/// Derives a replica id from the prover's address, the sector id and a ticket.
+/// The variable length inputs are length prefixed, so that distinct inputs never share a preimage.
+pub fn derive_replica_id<H: Hasher>(
+    prover_addr: &[u8],
+    sector_id: u64,
+    ticket: &[u8],
+) -> H::Domain {
+    let hash = Sha256::new()
+        .chain(REPLICA_ID_DST)
+        .chain(&(prover_addr.len() as u64).to_be_bytes()[..])
+        .chain(prover_addr)
+        .chain(&sector_id.to_be_bytes()[..])
+        .chain(&(ticket.len() as u64).to_be_bytes()[..])
+        .chain(ticket)
+        .finalize();
+
+    bytes_into_fr_repr_safe(hash.as_ref()).into()
+}
+
 fn sloth_encode<H: Hasher>(key: &H::Domain, ciphertext: &H::Domain) -> H::Domain {
     // TODO: validate this is how sloth should work in this case
     let k = (*key).into();
@@ -1,6 +1,13 @@
+use anyhow::{ensure, Context, Result};
 use blstrs::Scalar as Fr;
+use ff::PrimeField;
 use filecoin_hashers::Domain;
 
+/// Encodes `value` under `key` by field addition.
+///
+/// Both `key` and `value` must be canonical field elements: converting a domain element whose
+/// bytes are not below the field modulus panics, and even a conversion which succeeded would not
+/// round-trip. Use `encode_checked` for inputs which have not been validated.
 pub fn encode<T: Domain>(key: T, value: T) -> T {
     let value: Fr = value.into();
     let mut result: Fr = key.into();
@@ -9,10 +16,39 @@ pub fn encode<T: Domain>(key: T, value: T) -> T {
     result.into()
 }
 
+/// Like `encode`, but returns an error instead of panicking when `key` or `value` is not a
+/// canonical field element, i.e. when `decode(key, encode(key, value))` would not be `value`.
+pub fn encode_checked<T: Domain>(key: T, value: T) -> Result<T> {
+    let value = into_field_checked(value).context("invalid value")?;
+    let mut result = into_field_checked(key).context("invalid key")?;
+
+    encode_fr(&mut result, value);
+    Ok(result.into())
+}
+
+fn into_field_checked<T: Domain>(element: T) -> Result<Fr> {
+    let mut repr = <Fr as PrimeField>::Repr::default();
+    element.write_bytes(&mut repr)?;
+    let fr =
+        Fr::from_repr_vartime(repr).context("domain element is not a canonical field element")?;
+    ensure!(
+        T::from(fr) == element,
+        "domain element does not round-trip through the field"
+    );
+    Ok(fr)
+}
+
 pub fn encode_fr(key: &mut Fr, value: Fr) {
     *key += value;
 }
 
+/// Like `encode_fr`, but additionally adds `tweak`, so that values encoded under one tweak (e.g.
+/// one per layer) cannot be decoded under another. A zero tweak is equivalent to `encode_fr`.
+pub fn encode_fr_tweaked<F: PrimeField>(key: &mut F, value: F, tweak: F) {
+    *key += value;
+    *key += tweak;
+}
+
 pub fn decode<T: Domain>(key: T, value: T) -> T {
     let mut result: Fr = value.into();
     let key: Fr = key.into();
@@ -20,3 +56,61 @@ pub fn decode<T: Domain>(key: T, value: T) -> T {
     result -= key;
     result.into()
 }
+
+/// Reverses `encode_fr_tweaked`, given the same `key` and `tweak`.
+pub fn decode_fr_tweaked<F: PrimeField>(key: F, encoded: F, tweak: F) -> F {
+    let mut result = encoded;
+    result -= key;
+    result -= tweak;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use filecoin_hashers::sha256::Sha256Domain;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+    use storage_proofs_core::TEST_SEED;
+
+    #[test]
+    fn test_encode_fr_tweaked() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        let key = Fr::random(&mut *rng);
+        let value = Fr::random(&mut *rng);
+        let tweak = Fr::random(&mut *rng);
+
+        let mut encoded = key;
+        encode_fr_tweaked(&mut encoded, value, tweak);
+        assert_eq!(decode_fr_tweaked(key, encoded, tweak), value);
+
+        let other_tweak = Fr::random(&mut *rng);
+        assert_ne!(decode_fr_tweaked(key, encoded, other_tweak), value);
+
+        // A zero tweak matches the untweaked encoding.
+        let mut tweaked = key;
+        encode_fr_tweaked(&mut tweaked, value, Fr::zero());
+        let mut untweaked = key;
+        encode_fr(&mut untweaked, value);
+        assert_eq!(tweaked, untweaked);
+    }
+
+    #[test]
+    fn test_encode_checked() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        let key: Sha256Domain = Fr::random(&mut *rng).into();
+        let value: Sha256Domain = Fr::random(&mut *rng).into();
+        let encoded = encode_checked(key, value).expect("encode_checked failed");
+        assert_eq!(encoded, encode(key, value));
+        assert_eq!(decode(key, encoded), value);
+
+        // All bits set is larger than the field modulus.
+        let non_canonical = Sha256Domain([0xff; 32]);
+        assert!(encode_checked(key, non_canonical).is_err());
+        assert!(encode_checked(non_canonical, value).is_err());
+    }
+}
@@ -3,6 +3,7 @@ use num_bigint::BigUint;
 use num_traits::cast::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use storage_proofs_core::api_version::ApiVersion;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LayerChallenges {
@@ -25,19 +26,29 @@ impl LayerChallenges {
         self.max_count
     }
 
-    /// Derive all challenges.
+    /// Derive all challenges, following the rules of `api_version`. Provers and verifiers must
+    /// agree on the version, as it may change which nodes are challenged.
     pub fn derive<D: Domain>(
         &self,
+        api_version: ApiVersion,
         leaves: usize,
         replica_id: &D,
         seed: &[u8; 32],
         k: u8,
     ) -> Vec<usize> {
-        self.derive_internal(self.challenges_count_all(), leaves, replica_id, seed, k)
+        self.derive_internal(
+            api_version,
+            self.challenges_count_all(),
+            leaves,
+            replica_id,
+            seed,
+            k,
+        )
     }
 
     pub fn derive_internal<D: Domain>(
         &self,
+        api_version: ApiVersion,
         challenges_count: usize,
         leaves: usize,
         replica_id: &D,
@@ -46,12 +57,28 @@ impl LayerChallenges {
     ) -> Vec<usize> {
         assert!(leaves > 2, "Too few leaves: {}", leaves);
 
+        // Challenge derivation is consensus critical: a new `ApiVersion` must decide here whether
+        // it keeps the existing rules.
+        match api_version {
+            ApiVersion::V1_0_0 | ApiVersion::V1_1_0 => {
+                Self::derive_v1(challenges_count, leaves, replica_id, seed, k)
+            }
+        }
+    }
+
+    fn derive_v1<D: Domain>(
+        challenges_count: usize,
+        leaves: usize,
+        replica_id: &D,
+        seed: &[u8; 32],
+        k: u8,
+    ) -> Vec<usize> {
         (0..challenges_count)
             .map(|i| {
                 let j: u32 = ((challenges_count * k as usize) + i) as u32;
 
                 let hash = Sha256::new()
-                    .chain(replica_id.into_bytes())
+                    .chain(AsRef::<[u8]>::as_ref(replica_id))
                     .chain(seed)
                     .chain(&j.to_le_bytes())
                     .finalize();
@@ -111,7 +138,8 @@ mod test {
         for _layer in 1..=layers {
             let mut histogram = HashMap::new();
             for k in 0..partitions {
-                let challenges = challenges.derive(leaves, &replica_id, &seed, k as u8);
+                let challenges =
+                    challenges.derive(ApiVersion::V1_1_0, leaves, &replica_id, &seed, k as u8);
 
                 for challenge in challenges {
                     let counter = histogram.entry(challenge).or_insert(0);
@@ -146,6 +174,7 @@ mod test {
 
         for _layer in 1..=layers {
             let one_partition_challenges = LayerChallenges::new(layers, total_challenges).derive(
+                ApiVersion::V1_1_0,
                 leaves,
                 &replica_id,
                 &seed,
@@ -153,11 +182,41 @@ mod test {
             );
             let many_partition_challenges = (0..partitions)
                 .flat_map(|k| {
-                    LayerChallenges::new(layers, n).derive(leaves, &replica_id, &seed, k as u8)
+                    LayerChallenges::new(layers, n).derive(
+                        ApiVersion::V1_1_0,
+                        leaves,
+                        &replica_id,
+                        &seed,
+                        k as u8,
+                    )
                 })
                 .collect::<Vec<_>>();
 
             assert_eq!(one_partition_challenges, many_partition_challenges);
         }
     }
+
+    #[test]
+    fn challenge_derivation_is_stable() {
+        let challenges = LayerChallenges::new(11, 4);
+        let leaves = 1 << 20;
+        let replica_id = Sha256Domain([7; 32]);
+        let seed = [5; 32];
+
+        // Changing these values breaks consensus between provers and verifiers.
+        for api_version in &[ApiVersion::V1_0_0, ApiVersion::V1_1_0] {
+            assert_eq!(
+                challenges.derive(*api_version, leaves, &replica_id, &seed, 0),
+                vec![265299, 515107, 453657, 169573],
+                "api version {}",
+                api_version
+            );
+            assert_eq!(
+                challenges.derive(*api_version, leaves, &replica_id, &seed, 1),
+                vec![190936, 648991, 120942, 266462],
+                "api version {}",
+                api_version
+            );
+        }
+    }
 }
@@ -291,6 +291,19 @@ where
         self.base_graph.degree() + self.expansion_degree
     }
 
+    /// Early nodes have fewer base parents, but every node has all of its expansion parents.
+    fn degree_for_node(&self, node: usize) -> usize {
+        self.base_graph.degree_for_node(node) + self.expansion_degree
+    }
+
+    fn base_degree(&self) -> usize {
+        self.base_graph.degree()
+    }
+
+    fn expansion_degree(&self) -> usize {
+        self.expansion_degree
+    }
+
     #[inline]
     fn parents(&self, node: usize, parents: &mut [u32]) -> Result<()> {
         self.base_parents(node, &mut parents[..self.base_graph().degree()])?;
@@ -411,6 +424,10 @@ where
         &self.base_graph
     }
 
+    pub fn api_version(&self) -> ApiVersion {
+        self.api_version
+    }
+
     pub fn expansion_degree(&self) -> usize {
         self.expansion_degree
     }
@@ -495,6 +512,72 @@ mod tests {
         assert_eq!(shuffled.len(), (n * d) as usize);
     }
 
+    #[test]
+    fn test_degrees() {
+        let graph = StackedBucketGraph::<PoseidonHasher>::new_stacked(
+            64,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            [7; 32],
+            ApiVersion::V1_1_0,
+        )
+        .expect("stacked graph new failed");
+
+        assert_eq!(graph.base_degree(), BASE_DEGREE);
+        assert_eq!(Graph::expansion_degree(&graph), EXP_DEGREE);
+        assert_eq!(graph.degree(), BASE_DEGREE + EXP_DEGREE);
+    }
+
+    #[test]
+    fn test_layering_ignores_expansion_parents() {
+        let graph = StackedBucketGraph::<PoseidonHasher>::new_stacked(
+            256,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            [7; 32],
+            ApiVersion::V1_1_0,
+        )
+        .expect("stacked graph new failed");
+        let layers = graph.layering().expect("layering failed");
+
+        let mut layer_of = vec![None; graph.size()];
+        for (i, layer) in layers.iter().enumerate() {
+            for node in layer {
+                assert_eq!(layer_of[*node], None, "node {} is in two layers", node);
+                layer_of[*node] = Some(i);
+            }
+        }
+        assert!(layer_of.iter().all(Option::is_some));
+
+        let mut parents = vec![0; graph.degree()];
+        let mut later_exp_parents = 0;
+        for (i, layer) in layers.iter().enumerate() {
+            for node in layer {
+                graph.parents(*node, &mut parents).expect("parents failed");
+                let (base_parents, exp_parents) = parents.split_at(graph.base_degree());
+                for parent in base_parents.iter().map(|parent| *parent as usize) {
+                    if parent != *node {
+                        let parent_layer = layer_of[parent].expect("parent has no layer");
+                        assert!(
+                            parent_layer < i,
+                            "base parent {} of node {} is not in an earlier layer",
+                            parent,
+                            node
+                        );
+                    }
+                }
+                later_exp_parents += exp_parents
+                    .iter()
+                    .filter(|parent| **parent as usize > *node)
+                    .count();
+            }
+        }
+
+        // Expansion parents index the previous layer, so they may follow the node, which would
+        // be a cycle if they were layered like base parents.
+        assert!(later_exp_parents > 0);
+    }
+
     #[test]
     /// The initial implementation had a bug which prevented parents from ever falling in the later half of a sector.
     /// In fact, it is even worse than that, in the case of 64GiB sectors.
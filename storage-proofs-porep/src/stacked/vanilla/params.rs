@@ -3,7 +3,7 @@ use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
-use filecoin_hashers::{Domain, Hasher};
+use filecoin_hashers::{Domain, HashFunction, Hasher};
 use fr32::bytes_into_fr_repr_safe;
 use generic_array::typenum::{Unsigned, U2};
 use log::trace;
@@ -127,12 +127,13 @@ impl<T: Domain, S: Domain> PublicInputs<T, S> {
     pub fn challenges(
         &self,
         layer_challenges: &LayerChallenges,
+        api_version: ApiVersion,
         leaves: usize,
         partition_k: Option<usize>,
     ) -> Vec<usize> {
         let k = partition_k.unwrap_or(0);
 
-        layer_challenges.derive::<T>(leaves, &self.replica_id, &self.seed, k as u8)
+        layer_challenges.derive::<T>(api_version, leaves, &self.replica_id, &self.seed, k as u8)
     }
 }
 
@@ -329,7 +330,7 @@ pub type TransformedLayers<Tree, G> = (
 );
 
 /// Tau for a single parition.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Tau<D: Domain, E: Domain> {
     #[serde(bound = "")]
     pub comm_d: E,
@@ -337,6 +338,42 @@ pub struct Tau<D: Domain, E: Domain> {
     pub comm_r: D,
 }
 
+impl<D: Domain, E: Domain> Tau<D, E> {
+    /// Creates the tau of a replica from its `comm_d` and the roots `comm_r` is composed of.
+    pub fn from_components<H: Hasher<Domain = D>>(comm_d: E, comm_c: &D, comm_r_last: &D) -> Self {
+        Tau {
+            comm_d,
+            comm_r: compute_comm_r::<H>(comm_c, comm_r_last),
+        }
+    }
+
+    /// Serializes the tau into a fixed size format independent of serde, `comm_d || comm_r`,
+    /// each as the LittleEndian bytes of its domain element.
+    pub fn serialize(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        self.comm_d
+            .write_bytes(&mut bytes[..32])
+            .expect("domain elements are 32 bytes");
+        self.comm_r
+            .write_bytes(&mut bytes[32..])
+            .expect("domain elements are 32 bytes");
+        bytes
+    }
+
+    /// Deserializes a tau from the format written by `serialize`.
+    pub fn deserialize(bytes: &[u8; 64]) -> Result<Self> {
+        Ok(Tau {
+            comm_d: E::try_from_bytes(&bytes[..32])?,
+            comm_r: D::try_from_bytes(&bytes[32..])?,
+        })
+    }
+}
+
+/// Computes `comm_r = H(comm_c || comm_r_last)`.
+pub fn compute_comm_r<H: Hasher>(comm_c: &H::Domain, comm_r_last: &H::Domain) -> H::Domain {
+    H::Function::hash2(comm_c, comm_r_last)
+}
+
 /// Stored along side the sector on disk.
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PersistentAux<D> {
@@ -10,7 +10,7 @@ use bincode::deserialize;
 use blstrs::Scalar as Fr;
 use fdlimit::raise_fd_limit;
 use ff::PrimeField;
-use filecoin_hashers::{poseidon::PoseidonHasher, Domain, HashFunction, Hasher, PoseidonArity};
+use filecoin_hashers::{poseidon::PoseidonHasher, Domain, Hasher, PoseidonArity};
 use generic_array::typenum::{Unsigned, U0, U11, U2, U8};
 use lazy_static::lazy_static;
 use log::{error, info, trace, warn};
@@ -46,9 +46,9 @@ use crate::{
         graph::StackedBucketGraph,
         hash::hash_single_column,
         params::{
-            get_node, Labels, LabelsCache, PersistentAux, Proof, PublicInputs, PublicParams,
-            ReplicaColumnProof, Tau, TemporaryAux, TemporaryAuxCache, TransformedLayers,
-            BINARY_ARITY,
+            compute_comm_r, get_node, Labels, LabelsCache, PersistentAux, Proof, PublicInputs,
+            PublicParams, ReplicaColumnProof, Tau, TemporaryAux, TemporaryAuxCache,
+            TransformedLayers, BINARY_ARITY,
         },
         EncodingProof, LabelingProof,
     },
@@ -151,7 +151,12 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
                 trace!("proving partition {}/{}", k + 1, partition_count);
 
                 // Derive the set of challenges we are proving over.
-                let challenges = pub_inputs.challenges(layer_challenges, graph_size, Some(k));
+                let challenges = pub_inputs.challenges(
+                    layer_challenges,
+                    graph.api_version(),
+                    graph_size,
+                    Some(k),
+                );
 
                 // Stacked commitment specifics
                 challenges
@@ -851,8 +856,9 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
                 let data_node = <Tree::Hasher as Hasher>::Domain::try_from_bytes(data_node_bytes)
                     .expect("try from bytes failed");
 
-                let key_elem = <Tree::Hasher as Hasher>::Domain::try_from_bytes(&key.into_bytes())
-                    .expect("failed to convert key");
+                let key_elem =
+                    <Tree::Hasher as Hasher>::Domain::try_from_bytes(AsRef::<[u8]>::as_ref(&key))
+                        .expect("failed to convert key");
                 let encoded_node = encode::<<Tree::Hasher as Hasher>::Domain>(key_elem, data_node);
                 data_node_bytes.copy_from_slice(AsRef::<[u8]>::as_ref(&encoded_node));
 
@@ -1278,7 +1284,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         trace!("transform_and_replicate_layers");
         let nodes_count = graph.size();
 
-        assert_eq!(data.len(), nodes_count * NODE_SIZE);
+        assert_eq!(data.len(), graph.data_size());
         trace!("nodes count {}, data len {}", nodes_count, data.len());
 
         let tree_count = get_base_tree_count::<Tree>();
@@ -1433,15 +1439,8 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
         data.drop_data()?;
 
-        // comm_r = H(comm_c || comm_r_last)
-        let comm_r: <Tree::Hasher as Hasher>::Domain =
-            <Tree::Hasher as Hasher>::Function::hash2(&tree_c_root, &tree_r_last_root);
-
         Ok((
-            Tau {
-                comm_d: tree_d_root,
-                comm_r,
-            },
+            Tau::from_components::<Tree::Hasher>(tree_d_root, &tree_c_root, &tree_r_last_root),
             PersistentAux {
                 comm_c: tree_c_root,
                 comm_r_last: tree_r_last_root,
@@ -1716,9 +1715,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         let tree_r_last_root = tree_r_last.root();
         drop(tree_r_last);
 
-        // comm_r = H(comm_c || comm_r_last)
-        let comm_r: <Tree::Hasher as Hasher>::Domain =
-            <Tree::Hasher as Hasher>::Function::hash2(&tree_c_root, &tree_r_last_root);
+        let comm_r = compute_comm_r::<Tree::Hasher>(&tree_c_root, &tree_r_last_root);
 
         let p_aux = PersistentAux {
             comm_c: tree_c_root,
@@ -1743,9 +1740,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
         let existing_comm_r_last = existing_p_aux.comm_r_last;
 
-        // comm_r = H(comm_c || comm_r_last)
-        let comm_r: <Tree::Hasher as Hasher>::Domain =
-            <Tree::Hasher as Hasher>::Function::hash2(&tree_c_root, &existing_comm_r_last);
+        let comm_r = compute_comm_r::<Tree::Hasher>(&tree_c_root, &existing_comm_r_last);
 
         let p_aux = PersistentAux {
             comm_c: tree_c_root,
@@ -1,5 +1,5 @@
 use anyhow::ensure;
-use filecoin_hashers::{HashFunction, Hasher};
+use filecoin_hashers::Hasher;
 use log::trace;
 use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use storage_proofs_core::{
@@ -9,7 +9,7 @@ use storage_proofs_core::{
 use crate::stacked::vanilla::{
     challenges::ChallengeRequirements,
     graph::StackedBucketGraph,
-    params::{PrivateInputs, Proof, PublicInputs, PublicParams, SetupParams},
+    params::{compute_comm_r, PrivateInputs, Proof, PublicInputs, PublicParams, SetupParams},
     proof::StackedDrg,
 };
 
@@ -100,15 +100,19 @@ impl<'a, 'c, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> ProofScheme<'
             let actual_comm_r: <Tree::Hasher as Hasher>::Domain = {
                 let comm_c = proofs[0].comm_c();
                 let comm_r_last = proofs[0].comm_r_last();
-                <Tree::Hasher as Hasher>::Function::hash2(&comm_c, &comm_r_last)
+                compute_comm_r::<Tree::Hasher>(&comm_c, &comm_r_last)
             };
 
             if expected_comm_r != &actual_comm_r {
                 return false;
             }
 
-            let challenges =
-                pub_inputs.challenges(&pub_params.layer_challenges, graph.size(), Some(k));
+            let challenges = pub_inputs.challenges(
+                &pub_params.layer_challenges,
+                graph.api_version(),
+                graph.size(),
+                Some(k),
+            );
 
             proofs.par_iter().enumerate().all(|(i, proof)| {
                 trace!("verify challenge {}/{}", i + 1, challenges.len());
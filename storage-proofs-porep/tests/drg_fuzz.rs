@@ -0,0 +1,64 @@
+#![cfg(feature = "arbitrary")]
+
+use filecoin_hashers::{sha256::Sha256Hasher, Hasher};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use storage_proofs_core::{
+    api_version::ApiVersion,
+    arbitrary::{Arbitrary, Unstructured},
+    drgraph::{BucketGraph, BASE_DEGREE},
+    proof::ProofScheme,
+    TEST_SEED,
+};
+use storage_proofs_porep::drg::{self, DrgPoRep};
+
+/// Feeds arbitrary proofs to `DrgPoRep::verify`, which must reject them without panicking.
+#[test]
+fn fuzz_drg_porep_verify() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 16;
+
+    let sp = drg::SetupParams {
+        drg: drg::DrgParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: 0,
+            porep_id: [32; 32],
+        },
+        private: false,
+        challenges_count: 4,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let pp = DrgPoRep::<Sha256Hasher, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+    let mut bytes = vec![0u8; 1 << 16];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut bytes);
+        let mut u = Unstructured::new(&bytes);
+        let proof = match drg::Proof::<Sha256Hasher>::arbitrary(&mut u) {
+            Ok(proof) => proof,
+            Err(_) => continue,
+        };
+
+        let challenges_len = if rng.gen() {
+            proof.nodes.len()
+        } else {
+            rng.gen_range(0..=4)
+        };
+        let pub_inputs = drg::PublicInputs::<<Sha256Hasher as Hasher>::Domain> {
+            replica_id: Some(Default::default()),
+            challenges: (0..challenges_len)
+                .map(|_| rng.gen_range(0..2 * nodes))
+                .collect(),
+            tau: None,
+        };
+
+        // Errors are fine, panics are not. An empty proof trivially verifies no challenges.
+        if let Ok(verified) = DrgPoRep::verify(&pp, &pub_inputs, &proof) {
+            assert!(
+                !verified || pub_inputs.challenges.is_empty(),
+                "verified an arbitrary proof"
+            );
+        }
+    }
+}
@@ -1,7 +1,16 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
 use blstrs::Scalar as Fr;
 use ff::Field;
-use filecoin_hashers::{blake2s::Blake2sHasher, sha256::Sha256Hasher, Domain, Hasher};
+use filecoin_hashers::{
+    blake2s::Blake2sHasher, poseidon::PoseidonHasher, sha256::Sha256Hasher, Domain, Hasher,
+    PoseidonArity,
+};
 use fr32::fr_into_bytes;
+use generic_array::typenum::{U0, U2, U8};
 use merkletree::store::StoreConfig;
 use pretty_assertions::assert_eq;
 use rand::SeedableRng;
@@ -9,20 +18,21 @@ use rand_xorshift::XorShiftRng;
 use storage_proofs_core::{
     api_version::ApiVersion,
     cache_key::CacheKey,
-    drgraph::{BucketGraph, BASE_DEGREE},
-    merkle::{BinaryMerkleTree, MerkleTreeTrait},
+    drgraph::{graph_height, BucketGraph, Graph, BASE_DEGREE},
+    merkle::{generate_tree, BinaryMerkleTree, DiskTree, MerkleTreeTrait},
+    parameter_cache::ParameterSetMetadata,
     proof::ProofScheme,
     table_tests,
     test_helper::setup_replica,
-    util::default_rows_to_discard,
+    util::{default_rows_to_discard, NODE_SIZE},
     TEST_SEED,
 };
 use storage_proofs_porep::{
     drg::{self, DrgPoRep},
-    stacked::BINARY_ARITY,
+    stacked::{BINARY_ARITY, EXP_DEGREE},
     PoRep,
 };
-use tempfile::tempdir;
+use tempfile::{tempdir, TempDir};
 
 #[test]
 fn text_drg_porep_extract_all_sha256() {
@@ -155,6 +165,9 @@ fn test_extract<Tree: MerkleTreeTrait>() {
     assert_ne!(data, copied, "replication did not change data");
 
     for i in 0..nodes {
+        let extracted_domain = DrgPoRep::extract_domain(&pp, &replica_id, &mmapped_data, i)
+            .expect("failed to extract node domain from PoRep");
+
         DrgPoRep::extract(
             &pp,
             &replica_id,
@@ -164,6 +177,12 @@ fn test_extract<Tree: MerkleTreeTrait>() {
         )
         .expect("failed to extract node data from PoRep");
 
+        assert_eq!(
+            extracted_domain.into_bytes(),
+            &mmapped_data[i * node_size..(i + 1) * node_size],
+            "extract_domain does not match extract"
+        );
+
         // This is no longer working, so the assertion is now incorrect.
         //let original_data = data_at_node(&data, i).expect("data_at_node failure");
         //let extracted_data = &mmapped_data[i * node_size..(i * node_size) + node_size];
@@ -381,3 +400,1035 @@ fn test_drg_porep_verify_fails_on_wrong_parents() {
     test_prove_verify_aux::<BinaryMerkleTree<Sha256Hasher>>(8, 5, false, true);
     test_prove_verify_aux::<BinaryMerkleTree<Blake2sHasher>>(8, 5, false, true);
 }
+
+#[test]
+fn test_drg_porep_prove_dedup_sha256() {
+    test_prove_dedup::<BinaryMerkleTree<Sha256Hasher>>();
+}
+
+#[test]
+fn test_drg_porep_prove_dedup_blake2s() {
+    test_prove_dedup::<BinaryMerkleTree<Blake2sHasher>>();
+}
+
+fn test_prove_dedup<Tree: MerkleTreeTrait>() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let replica = TestReplica::<Tree::Hasher>::new(&mut rng, 8, 1);
+    let pp = &replica.pp;
+
+    // All parents of node 1 are node 0.
+    let (pub_inputs, proof) = replica.prove(vec![1]);
+    let dedup_proof =
+        DrgPoRep::<Tree::Hasher, _>::prove_dedup(pp, &pub_inputs, &replica.priv_inputs())
+            .expect("dedup proving failed");
+
+    assert_eq!(dedup_proof.replica_parents[0].len(), BASE_DEGREE);
+    assert_eq!(dedup_proof.parent_proofs[0].len(), 1);
+
+    let proof_bytes = serde_json::to_vec(&proof).expect("failed to serialize proof");
+    let dedup_proof_bytes =
+        serde_json::to_vec(&dedup_proof).expect("failed to serialize dedup proof");
+    assert!(dedup_proof_bytes.len() < proof_bytes.len());
+
+    let expanded = dedup_proof.expand().expect("failed to expand dedup proof");
+    assert_eq!(
+        expanded
+            .replica_parents
+            .iter()
+            .map(|parents| parents.iter().map(|(p, _)| *p).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        proof
+            .replica_parents
+            .iter()
+            .map(|parents| parents.iter().map(|(p, _)| *p).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+    );
+
+    assert!(
+        DrgPoRep::<Tree::Hasher, _>::verify_dedup(pp, &pub_inputs, &dedup_proof)
+            .expect("verification failed"),
+        "failed to verify dedup proof"
+    );
+
+    let mut broken_proof = dedup_proof;
+    broken_proof.replica_parents[0][0].1 = 1;
+    assert!(
+        !DrgPoRep::<Tree::Hasher, _>::verify_dedup(pp, &pub_inputs, &broken_proof)
+            .expect("verification failed"),
+        "verified dedup proof referencing a missing parent proof"
+    );
+}
+
+#[test]
+fn test_drg_porep_tau_eq() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 16;
+
+    let replica_id = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    let data: Vec<u8> = (0..nodes)
+        .flat_map(|_| fr_into_bytes(&Fr::random(&mut rng)))
+        .collect();
+
+    let replicate =
+        |replica_id| TestReplica::<Sha256Hasher>::from_data(replica_id, data.clone(), 1);
+    let tau_a = replicate(replica_id).tau;
+    let tau_b = replicate(replica_id).tau;
+    assert_eq!(tau_a, tau_b);
+
+    let mut taus = HashSet::new();
+    taus.insert(tau_a);
+    assert!(taus.contains(&tau_b));
+
+    let other_replica_id = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    let tau_c = replicate(other_replica_id).tau;
+    assert_ne!(tau_a, tau_c);
+    assert!(!taus.contains(&tau_c));
+}
+
+/// Replicates the same data with the same replica id twice and asserts that both runs produce
+/// identical commitments and identical encoded replicas.
+fn assert_replication_deterministic<H: 'static + Hasher>() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 64;
+
+    let replica_id = H::Domain::random(&mut rng);
+    let data: Vec<u8> = (0..nodes)
+        .flat_map(|_| fr_into_bytes(&Fr::random(&mut rng)))
+        .collect();
+
+    let a = TestReplica::<H>::from_data(replica_id, data.clone(), 1);
+    let b = TestReplica::<H>::from_data(replica_id, data, 1);
+
+    assert_eq!(a.tau.comm_d, b.tau.comm_d, "data roots differ");
+    assert_eq!(a.tau.comm_r, b.tau.comm_r, "replica roots differ");
+    assert_eq!(a.replica(), b.replica(), "encoded replicas differ");
+}
+
+#[test]
+fn test_drg_porep_replication_deterministic_sha256() {
+    assert_replication_deterministic::<Sha256Hasher>();
+}
+
+#[test]
+fn test_drg_porep_replication_deterministic_blake2s() {
+    assert_replication_deterministic::<Blake2sHasher>();
+}
+
+#[test]
+fn test_drg_porep_replication_deterministic_poseidon() {
+    assert_replication_deterministic::<PoseidonHasher>();
+}
+
+#[test]
+fn test_drg_porep_challenges_near_usize_max() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 8;
+    let replica = TestReplica::<Sha256Hasher>::new(&mut rng, nodes, 2);
+    let pp = &replica.pp;
+
+    // Proving reduces the challenges into the graph.
+    let challenges = vec![usize::MAX, usize::MAX - 1];
+    let (pub_inputs, proof) = replica.prove(challenges.clone());
+
+    // Unreduced challenges are out of range and must be rejected rather than panic.
+    assert!(
+        !DrgPoRep::<Sha256Hasher, _>::verify(pp, &pub_inputs, &proof).expect("verification failed"),
+        "verified in error -- with out of range challenges"
+    );
+
+    let reduced_inputs = drg::PublicInputs {
+        challenges: challenges.iter().map(|c| c % nodes).collect(),
+        ..pub_inputs
+    };
+    assert!(
+        DrgPoRep::<Sha256Hasher, _>::verify(pp, &reduced_inputs, &proof)
+            .expect("verification failed"),
+        "failed to verify"
+    );
+
+    let mut truncated_proof = proof;
+    truncated_proof.nodes.pop();
+    assert!(
+        !DrgPoRep::<Sha256Hasher, _>::verify(pp, &reduced_inputs, &truncated_proof)
+            .expect("verification failed"),
+        "verified in error -- with a missing opening"
+    );
+}
+
+#[test]
+fn test_drg_porep_prove_rejects_invalid_challenges() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 8;
+    let replica = TestReplica::<Sha256Hasher>::new(&mut rng, nodes, 1);
+    let pp = &replica.pp;
+
+    let priv_inputs = replica.priv_inputs();
+    let prove = |challenges: Vec<usize>| {
+        DrgPoRep::<Sha256Hasher, _>::prove(pp, &replica.pub_inputs(challenges), &priv_inputs)
+    };
+
+    let err = prove(vec![0]).expect_err("proved node 0");
+    assert_eq!(
+        err.to_string(),
+        "challenge 0 maps to node 0, which has no parents and cannot be challenged"
+    );
+
+    let err = prove(vec![nodes]).expect_err("proved a challenge reducing to node 0");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "challenge {} maps to node 0, which has no parents and cannot be challenged",
+            nodes
+        )
+    );
+
+    let err = prove(vec![1, 2]).expect_err("proved too many challenges");
+    assert_eq!(
+        err.to_string(),
+        "too many challenges: 2 supplied, but at most 1 are allowed"
+    );
+
+    prove(vec![1]).expect("proving failed");
+}
+
+/// A replica of random data, with everything needed to prove against it. The replica and its
+/// trees are removed once this is dropped.
+struct TestReplica<H: 'static + Hasher> {
+    pp: drg::PublicParams<H, BucketGraph<H>>,
+    replica_id: H::Domain,
+    /// The data before replication.
+    data: Vec<u8>,
+    tau: drg::Tau<H::Domain>,
+    aux: drg::ProverAux<H>,
+    replica_path: PathBuf,
+    _cache_dir: TempDir,
+}
+
+impl<H: 'static + Hasher> TestReplica<H> {
+    /// Replicates `nodes` of random data, allowing up to `challenges_count` challenges per proof.
+    fn new(rng: &mut XorShiftRng, nodes: usize, challenges_count: usize) -> Self {
+        Self::replicate(rng, nodes, challenges_count, false)
+    }
+
+    /// Like `new`, but keeps the parent table used during replication.
+    fn with_parent_table(rng: &mut XorShiftRng, nodes: usize, challenges_count: usize) -> Self {
+        Self::replicate(rng, nodes, challenges_count, true)
+    }
+
+    /// Replicates `data` with the given `replica_id`.
+    fn from_data(replica_id: H::Domain, data: Vec<u8>, challenges_count: usize) -> Self {
+        Self::replicate_data(replica_id, data, challenges_count, false)
+    }
+
+    fn replicate(
+        rng: &mut XorShiftRng,
+        nodes: usize,
+        challenges_count: usize,
+        keep_parent_table: bool,
+    ) -> Self {
+        let replica_id = H::Domain::random(rng);
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes(&Fr::random(&mut *rng)))
+            .collect();
+
+        Self::replicate_data(replica_id, data, challenges_count, keep_parent_table)
+    }
+
+    fn replicate_data(
+        replica_id: H::Domain,
+        data: Vec<u8>,
+        challenges_count: usize,
+        keep_parent_table: bool,
+    ) -> Self {
+        let nodes = data.len() / NODE_SIZE;
+        let cache_dir = tempdir().expect("tempdir failure");
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            default_rows_to_discard(nodes, BINARY_ARITY),
+        );
+        let replica_path = cache_dir.path().join("replica-path");
+        let mut mmapped_data = setup_replica(&data, &replica_path);
+
+        let sp = drg::SetupParams {
+            drg: drg::DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                porep_id: [32; 32],
+            },
+            private: false,
+            challenges_count,
+            api_version: ApiVersion::V1_1_0,
+        };
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let (tau, aux) = if keep_parent_table {
+            DrgPoRep::<H, _>::replicate_with_parent_table(
+                &pp,
+                &replica_id,
+                (mmapped_data.as_mut()).into(),
+                None,
+                config,
+                replica_path.clone(),
+            )
+        } else {
+            DrgPoRep::<H, _>::replicate(
+                &pp,
+                &replica_id,
+                (mmapped_data.as_mut()).into(),
+                None,
+                config,
+                replica_path.clone(),
+            )
+        }
+        .expect("replication failed");
+
+        TestReplica {
+            pp,
+            replica_id,
+            data,
+            tau,
+            aux,
+            replica_path,
+            _cache_dir: cache_dir,
+        }
+    }
+
+    /// Reads the encoded replica.
+    fn replica(&self) -> Vec<u8> {
+        fs::read(&self.replica_path).expect("failed to read replica")
+    }
+
+    fn priv_inputs(&self) -> drg::PrivateInputs<'_, H> {
+        drg::PrivateInputs {
+            tree_d: &self.aux.tree_d,
+            tree_r: &self.aux.tree_r,
+            tree_r_config_rows_to_discard: default_rows_to_discard(
+                self.pp.graph.size(),
+                BINARY_ARITY,
+            ),
+        }
+    }
+
+    fn pub_inputs(&self, challenges: Vec<usize>) -> drg::PublicInputs<H::Domain> {
+        drg::PublicInputs {
+            replica_id: Some(self.replica_id),
+            challenges,
+            tau: Some(self.tau),
+        }
+    }
+
+    /// Proves `challenges` against this replica.
+    fn prove(&self, challenges: Vec<usize>) -> (drg::PublicInputs<H::Domain>, drg::Proof<H>) {
+        let pub_inputs = self.pub_inputs(challenges);
+        let proof = DrgPoRep::<H, _>::prove(&self.pp, &pub_inputs, &self.priv_inputs())
+            .expect("proving failed");
+        (pub_inputs, proof)
+    }
+}
+
+/// Replicates random data and proves a single challenge against it.
+fn replicate_and_prove(
+    rng: &mut XorShiftRng,
+    nodes: usize,
+    challenge: usize,
+) -> (
+    drg::PublicParams<Sha256Hasher, BucketGraph<Sha256Hasher>>,
+    drg::PublicInputs<<Sha256Hasher as Hasher>::Domain>,
+    drg::Proof<Sha256Hasher>,
+) {
+    let (pp, mut proofs) = replicate_and_prove_each(rng, nodes, &[&[challenge]]);
+    let (pub_inputs, proof) = proofs.pop().expect("one proof per challenge set");
+    (pp, pub_inputs, proof)
+}
+
+/// Replicates random data and proves each set of challenges against it, in a separate `prove`.
+fn replicate_and_prove_each(
+    rng: &mut XorShiftRng,
+    nodes: usize,
+    challenge_sets: &[&[usize]],
+) -> (
+    drg::PublicParams<Sha256Hasher, BucketGraph<Sha256Hasher>>,
+    Vec<(
+        drg::PublicInputs<<Sha256Hasher as Hasher>::Domain>,
+        drg::Proof<Sha256Hasher>,
+    )>,
+) {
+    let challenges_count = challenge_sets
+        .iter()
+        .map(|set| set.len())
+        .max()
+        .unwrap_or(1);
+    let replica = TestReplica::<Sha256Hasher>::new(rng, nodes, challenges_count);
+
+    let proofs = challenge_sets
+        .iter()
+        .map(|challenges| replica.prove(challenges.to_vec()))
+        .collect();
+
+    (replica.pp, proofs)
+}
+
+#[test]
+fn test_drg_porep_verify_structure_only() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (pp, pub_inputs, proof) = replicate_and_prove(&mut rng, 16, 7);
+    assert!(
+        DrgPoRep::<Sha256Hasher, _>::verify_structure_only(&pp, &pub_inputs, &proof)
+            .expect("verify_structure_only failed")
+    );
+
+    // Data which does not decode from the replica is only caught by a full `verify`.
+    let mut wrong_data = proof.clone();
+    wrong_data.nodes[0].data = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    assert!(
+        DrgPoRep::<Sha256Hasher, _>::verify_structure_only(&pp, &pub_inputs, &wrong_data)
+            .expect("verify_structure_only failed")
+    );
+    assert!(
+        !DrgPoRep::<Sha256Hasher, _>::verify(&pp, &pub_inputs, &wrong_data).expect("verify failed")
+    );
+
+    // Open a parent with the path of another one.
+    let mut wrong_path = proof;
+    let parents = &mut wrong_path.replica_parents[0];
+    let other = parents
+        .iter()
+        .position(|(p, _)| *p != parents[0].0)
+        .expect("all parents are the same node");
+    parents[other].1 = parents[0].1.clone();
+    assert!(
+        !DrgPoRep::<Sha256Hasher, _>::verify_structure_only(&pp, &pub_inputs, &wrong_path)
+            .expect("verify_structure_only failed")
+    );
+}
+
+#[test]
+fn test_drg_porep_prove_matches_single_challenge_proofs() {
+    // Proving several challenges at once reuses one parents buffer, which must not leak between
+    // challenges.
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let challenges = [3, 9, 14];
+    let (pp, proofs) = replicate_and_prove_each(&mut rng, 16, &[&challenges, &[3], &[9], &[14]]);
+
+    let (pub_inputs, proof) = &proofs[0];
+    assert!(DrgPoRep::<Sha256Hasher, _>::verify(&pp, pub_inputs, proof).expect("verify failed"));
+
+    for (i, (_, single)) in proofs[1..].iter().enumerate() {
+        assert_eq!(proof.replica_nodes[i], single.replica_nodes[0]);
+        assert_eq!(proof.nodes[i], single.nodes[0]);
+        assert_eq!(proof.replica_parents[i], single.replica_parents[0]);
+    }
+}
+
+#[test]
+fn test_drg_porep_proofs_for_identical_inputs_are_equal() {
+    let (pp, pub_inputs, proof) =
+        replicate_and_prove(&mut XorShiftRng::from_seed(TEST_SEED), 16, 7);
+    let (_, _, other) = replicate_and_prove(&mut XorShiftRng::from_seed(TEST_SEED), 16, 7);
+    assert!(DrgPoRep::<Sha256Hasher, _>::verify(&pp, &pub_inputs, &proof).expect("verify failed"));
+    assert_eq!(proof, other);
+
+    let (_, _, other_challenge) =
+        replicate_and_prove(&mut XorShiftRng::from_seed(TEST_SEED), 16, 8);
+    assert_ne!(proof, other_challenge);
+
+    let mut tampered = proof.clone();
+    tampered.replica_parents[0][0].0 += 1;
+    assert_ne!(proof, tampered);
+}
+
+#[test]
+fn test_drg_porep_multi_replica_prover() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let challenges = vec![3, 9, 14];
+
+    // Replicas of the same size share their public parameters.
+    let replicas: Vec<_> = (0..2)
+        .map(|_| TestReplica::<Sha256Hasher>::new(&mut rng, 16, challenges.len()))
+        .collect();
+
+    let mut prover = drg::MultiReplicaProver::new(&replicas[0].pp);
+    let inputs: Vec<_> = replicas
+        .iter()
+        .map(|replica| (replica.replica_id, replica.priv_inputs()))
+        .collect();
+    let proofs = prover
+        .prove_all(&challenges, &inputs)
+        .expect("proving failed");
+    assert_eq!(proofs.len(), replicas.len());
+
+    // Both replicas were challenged at the same nodes, whose parents were only computed once.
+    assert_eq!(prover.cached_parents(), challenges.len());
+
+    for (replica, proof) in replicas.iter().zip(&proofs) {
+        assert!(DrgPoRep::<Sha256Hasher, _>::verify(
+            prover.pub_params(),
+            &replica.pub_inputs(challenges.clone()),
+            proof
+        )
+        .expect("verify failed"));
+    }
+}
+
+#[test]
+fn test_drg_porep_aggregate_proof() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+
+    let (pp_a, pub_inputs_a, proof_a) = replicate_and_prove(&mut rng, 8, 3);
+    let (pp_b, pub_inputs_b, mut proof_b) = replicate_and_prove(&mut rng, 16, 5);
+
+    let pub_params = vec![pp_a, pp_b];
+    let pub_inputs = vec![pub_inputs_a, pub_inputs_b];
+    let metadata = pub_params.iter().map(|pp| pp.graph.topology()).collect();
+
+    let aggregate = drg::AggregateProof::new(vec![proof_a, proof_b.clone()], metadata);
+    assert_eq!(
+        aggregate
+            .verify_all(&pub_params, &pub_inputs)
+            .expect("verification failed"),
+        None
+    );
+
+    let bytes = serde_json::to_vec(&aggregate).expect("failed to serialize aggregate proof");
+    let deserialized: drg::AggregateProof<Sha256Hasher> =
+        serde_json::from_slice(&bytes).expect("failed to deserialize aggregate proof");
+    assert_eq!(deserialized.metadata, aggregate.metadata);
+    assert_eq!(
+        deserialized
+            .verify_all(&pub_params, &pub_inputs)
+            .expect("verification failed"),
+        None
+    );
+
+    proof_b.nodes[0].data = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    let tampered = drg::AggregateProof::new(
+        vec![aggregate.proofs[0].clone(), proof_b],
+        aggregate.metadata.clone(),
+    );
+    assert_eq!(
+        tampered
+            .verify_all(&pub_params, &pub_inputs)
+            .expect("verification failed"),
+        Some(1)
+    );
+
+    let mut swapped_metadata = aggregate.metadata.clone();
+    swapped_metadata.swap(0, 1);
+    let mismatched = drg::AggregateProof::new(aggregate.proofs, swapped_metadata);
+    assert_eq!(
+        mismatched
+            .verify_all(&pub_params, &pub_inputs)
+            .expect("verification failed"),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_derive_replica_id() {
+    let prover_addr = b"t01234";
+    let ticket = [7u8; 32];
+
+    let id = drg::derive_replica_id::<Sha256Hasher>(prover_addr, 1, &ticket);
+    assert_eq!(
+        id,
+        drg::derive_replica_id::<Sha256Hasher>(prover_addr, 1, &ticket)
+    );
+
+    assert_ne!(
+        id,
+        drg::derive_replica_id::<Sha256Hasher>(prover_addr, 2, &ticket)
+    );
+    assert_ne!(
+        id,
+        drg::derive_replica_id::<Sha256Hasher>(b"t01235", 1, &ticket)
+    );
+    assert_ne!(
+        id,
+        drg::derive_replica_id::<Sha256Hasher>(prover_addr, 1, &[8u8; 32])
+    );
+
+    // Moving bytes between the address and the ticket must change the id.
+    assert_ne!(
+        drg::derive_replica_id::<Sha256Hasher>(b"ab", 1, b"c"),
+        drg::derive_replica_id::<Sha256Hasher>(b"a", 1, b"bc")
+    );
+
+    let poseidon_id = drg::derive_replica_id::<PoseidonHasher>(prover_addr, 1, &ticket);
+    assert_eq!(
+        AsRef::<[u8]>::as_ref(&poseidon_id),
+        AsRef::<[u8]>::as_ref(&id)
+    );
+}
+
+#[test]
+fn test_drg_porep_verify_with_verification_params() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (pp, pub_inputs, proof) = replicate_and_prove(&mut rng, 16, 7);
+
+    let topology = pp.graph.topology();
+    let verification_pp =
+        drg::PublicParams::<Sha256Hasher, BucketGraph<Sha256Hasher>>::for_verification(
+            &topology,
+            pp.private,
+            pp.challenges_count,
+        )
+        .expect("failed to build verification params");
+    assert_eq!(verification_pp.graph, pp.graph);
+
+    assert!(
+        DrgPoRep::verify(&verification_pp, &pub_inputs, &proof).expect("verification failed"),
+        "failed to verify against verification params"
+    );
+}
+
+#[test]
+fn test_drg_porep_tau_commitments() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let comm_d = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    let comm_r = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+
+    let tau = drg::Tau::new(drg::CommD(comm_d), drg::CommR(comm_r));
+    assert_eq!(tau.comm_d.0, comm_d);
+    assert_eq!(tau.comm_r.0, comm_r);
+
+    // The wrappers are transparent, so the serialized form is unchanged.
+    let json = serde_json::to_value(&tau).expect("failed to serialize tau");
+    assert_eq!(
+        json["comm_d"],
+        serde_json::to_value(&comm_d).expect("failed to serialize comm_d")
+    );
+    assert_eq!(
+        json["comm_r"],
+        serde_json::to_value(&comm_r).expect("failed to serialize comm_r")
+    );
+
+    let deserialized: drg::Tau<<Sha256Hasher as Hasher>::Domain> =
+        serde_json::from_value(json).expect("failed to deserialize tau");
+    assert_eq!(deserialized, tau);
+}
+
+#[test]
+fn test_drg_porep_proof_serialize_into() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (_, _, proof) = replicate_and_prove(&mut rng, 16, 7);
+
+    let mut data_proof_bytes = Vec::new();
+    proof.nodes[0]
+        .serialize_into(&mut data_proof_bytes)
+        .expect("failed to stream data proof");
+    assert_eq!(
+        data_proof_bytes,
+        bincode::serialize(&proof.nodes[0]).expect("failed to serialize data proof")
+    );
+
+    let mut proof_bytes = Vec::new();
+    proof
+        .serialize_into(&mut proof_bytes)
+        .expect("failed to stream proof");
+    assert_eq!(
+        proof_bytes,
+        bincode::serialize(&proof).expect("failed to serialize proof")
+    );
+}
+
+#[test]
+fn test_drg_porep_proof_deserialize_from() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 16;
+    let (_, _, proof) = replicate_and_prove(&mut rng, nodes, 7);
+    let height = graph_height::<U2>(nodes) - 1;
+
+    let mut bytes = Vec::new();
+    proof
+        .serialize_into(&mut bytes)
+        .expect("failed to stream proof");
+
+    let deserialized =
+        drg::Proof::<Sha256Hasher>::deserialize_from(&bytes[..], height, BASE_DEGREE, 1)
+            .expect("failed to read proof");
+    let mut deserialized_bytes = Vec::new();
+    deserialized
+        .serialize_into(&mut deserialized_bytes)
+        .expect("failed to stream proof");
+    assert_eq!(deserialized_bytes, bytes);
+    assert_eq!(deserialized, proof);
+
+    // Truncated input.
+    assert!(drg::Proof::<Sha256Hasher>::deserialize_from(
+        &bytes[..bytes.len() - 1],
+        height,
+        BASE_DEGREE,
+        1
+    )
+    .is_err());
+
+    // Unexpected shapes.
+    assert!(
+        drg::Proof::<Sha256Hasher>::deserialize_from(&bytes[..], height, BASE_DEGREE, 2).is_err()
+    );
+    assert!(
+        drg::Proof::<Sha256Hasher>::deserialize_from(&bytes[..], height, BASE_DEGREE - 1, 1)
+            .is_err()
+    );
+    assert!(
+        drg::Proof::<Sha256Hasher>::deserialize_from(&bytes[..], height - 1, BASE_DEGREE, 1)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_drg_porep_verify_reported() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (pp, pub_inputs, proof) = replicate_and_prove(&mut rng, 16, 7);
+
+    let (valid, report) = DrgPoRep::<Sha256Hasher, _>::verify_reported(&pp, &pub_inputs, &proof)
+        .expect("verification failed");
+    assert!(valid);
+    assert_eq!(report.total_challenges, 1);
+    // One proof of the replica node, one per parent, and one of the data node.
+    assert_eq!(report.merkle_validations, 1 + BASE_DEGREE + 1);
+    assert_eq!(
+        report.proof_bytes as u64,
+        bincode::serialized_size(&proof).expect("failed to size proof")
+    );
+
+    // A proof of the wrong challenge fails before any merkle proof is validated.
+    let mut wrong_challenge = pub_inputs.clone();
+    wrong_challenge.challenges = vec![8];
+    let (valid, report) =
+        DrgPoRep::<Sha256Hasher, _>::verify_reported(&pp, &wrong_challenge, &proof)
+            .expect("verification failed");
+    assert!(!valid);
+    assert_eq!(report.merkle_validations, 0);
+}
+
+#[test]
+fn test_drg_porep_prove_requires_replica_id() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let replica = TestReplica::<Sha256Hasher>::new(&mut rng, 8, 1);
+
+    let pub_inputs = drg::PublicInputs {
+        replica_id: None,
+        ..replica.pub_inputs(vec![1])
+    };
+    let err = DrgPoRep::<Sha256Hasher, _>::prove(&replica.pp, &pub_inputs, &replica.priv_inputs())
+        .expect_err("proved without a replica_id");
+    assert_eq!(err.to_string(), "replica_id is required to prove");
+}
+
+#[test]
+fn test_drg_porep_verify_requires_replica_id() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (pp, mut pub_inputs, proof) = replicate_and_prove(&mut rng, 8, 1);
+
+    pub_inputs.replica_id = None;
+    let err = DrgPoRep::<Sha256Hasher, _>::verify(&pp, &pub_inputs, &proof)
+        .expect_err("verified without a replica_id");
+    assert_eq!(err.to_string(), "replica_id is required to verify");
+}
+
+#[test]
+fn test_drg_porep_verify_rejects_empty_proof() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 8;
+    let (pp, pub_inputs, proof) = replicate_and_prove(&mut rng, nodes, 1);
+    assert!(!proof.is_empty());
+
+    let height = graph_height::<U2>(nodes) - 1;
+    let empty = drg::Proof::<Sha256Hasher>::new_empty(height, pp.graph.degree(), 1);
+    assert!(empty.is_empty());
+
+    let err = DrgPoRep::<Sha256Hasher, _>::verify(&pp, &pub_inputs, &empty)
+        .expect_err("verified an empty proof");
+    assert_eq!(err.to_string(), "cannot verify an empty placeholder proof");
+}
+
+#[test]
+fn test_data_proof_new_matches_proof_size() {
+    test_data_proof_new_matches_proof_size_aux::<U2>(64);
+    test_data_proof_new_matches_proof_size_aux::<U8>(64);
+}
+
+fn test_data_proof_new_matches_proof_size_aux<U: 'static + PoseidonArity>(nodes: usize) {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (_, tree) = generate_tree::<DiskTree<Sha256Hasher, U, U0, U0>, _>(&mut rng, nodes, None);
+
+    let real = drg::DataProof::<Sha256Hasher, U> {
+        proof: tree.gen_proof(1).expect("gen_proof failure"),
+        data: <Sha256Hasher as Hasher>::Domain::random(&mut rng),
+    };
+    let empty = drg::DataProof::<Sha256Hasher, U>::new(graph_height::<U>(nodes) - 1);
+    assert!(empty.is_empty());
+
+    assert_eq!(
+        bincode::serialized_size(&empty).expect("failed to size empty proof"),
+        bincode::serialized_size(&real).expect("failed to size proof")
+    );
+}
+
+#[test]
+fn test_drg_porep_verify_rejects_roots_not_matching_tau() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (pp, pub_inputs, proof) = replicate_and_prove(&mut rng, 8, 1);
+    assert!(DrgPoRep::<Sha256Hasher, _>::verify(&pp, &pub_inputs, &proof).expect("verify failed"));
+
+    let mut bad_data_root = proof.clone();
+    bad_data_root.data_root = proof.replica_root;
+    assert!(
+        !DrgPoRep::<Sha256Hasher, _>::verify(&pp, &pub_inputs, &bad_data_root)
+            .expect("verify failed")
+    );
+
+    let mut bad_replica_root = proof.clone();
+    bad_replica_root.replica_root = proof.data_root;
+    assert!(
+        !DrgPoRep::<Sha256Hasher, _>::verify(&pp, &pub_inputs, &bad_replica_root)
+            .expect("verify failed")
+    );
+}
+
+#[test]
+fn test_drg_porep_plan_prove() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (pp, pub_inputs, proof) = replicate_and_prove(&mut rng, 16, 7);
+
+    let plan =
+        DrgPoRep::<Sha256Hasher, _>::plan_prove(&pp, &pub_inputs).expect("plan_prove failed");
+
+    let proof_count = proof.replica_nodes.len()
+        + proof.replica_parents.iter().map(Vec::len).sum::<usize>()
+        + proof.nodes.len();
+    assert_eq!(plan.challenges, 1);
+    assert_eq!(plan.merkle_proofs, proof_count);
+    assert_eq!(plan.block_decodes, 1);
+    assert_eq!(
+        plan.proof_bytes as u64,
+        bincode::serialized_size(&proof).expect("failed to size proof")
+    );
+
+    let mut parents: Vec<u32> = proof.replica_parents[0].iter().map(|(p, _)| *p).collect();
+    parents.sort_unstable();
+    parents.dedup();
+    assert_eq!(plan.gen_proof_calls, 2 + parents.len());
+    assert_eq!(plan.parent_reads, parents.len() + BASE_DEGREE);
+
+    let mut zero_challenge = pub_inputs;
+    zero_challenge.challenges = vec![16];
+    assert!(DrgPoRep::<Sha256Hasher, _>::plan_prove(&pp, &zero_challenge).is_err());
+}
+
+#[test]
+fn test_drg_porep_verify_with_deadline() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (pp, pub_inputs, proof) = replicate_and_prove(&mut rng, 16, 7);
+
+    let passed = Instant::now();
+    assert_eq!(
+        DrgPoRep::<Sha256Hasher, _>::verify_with_deadline(&pp, &pub_inputs, &proof, passed)
+            .expect("verification failed"),
+        None
+    );
+
+    let generous = Instant::now() + Duration::from_secs(3600);
+    assert_eq!(
+        DrgPoRep::<Sha256Hasher, _>::verify_with_deadline(&pp, &pub_inputs, &proof, generous)
+            .expect("verification failed"),
+        Some(true)
+    );
+
+    let mut tampered = proof;
+    tampered.nodes[0].data = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    assert_eq!(
+        DrgPoRep::<Sha256Hasher, _>::verify_with_deadline(&pp, &pub_inputs, &tampered, generous)
+            .expect("verification failed"),
+        Some(false)
+    );
+}
+
+#[test]
+fn test_drg_decode_matches_sequential_decode_block() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let nodes = 256;
+
+    let data: Vec<u8> = (0..nodes)
+        .flat_map(|_| fr_into_bytes(&Fr::random(&mut rng)))
+        .collect();
+    let replica_id = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    let graph =
+        BucketGraph::<Sha256Hasher>::new(nodes, BASE_DEGREE, 0, [32; 32], ApiVersion::V1_1_0)
+            .expect("bucket graph new failed");
+
+    // Every node is decoded from the still encoded parents, so decoding them independently of
+    // each other yields the same bytes.
+    let sequential: Vec<u8> = (0..nodes)
+        .flat_map(|node| {
+            drg::decode_block(&graph, &replica_id, &data, None, node)
+                .expect("decode_block failed")
+                .into_bytes()
+        })
+        .collect();
+
+    let mut parallel = data;
+    drg::decode(&graph, &replica_id, &mut parallel, None).expect("decode failed");
+
+    assert_eq!(parallel, sequential);
+}
+
+#[test]
+fn test_drg_porep_identifier_is_versioned() {
+    let sp = drg::SetupParams {
+        drg: drg::DrgParams {
+            nodes: 16,
+            degree: BASE_DEGREE,
+            expansion_degree: 0,
+            porep_id: [32; 32],
+        },
+        private: false,
+        challenges_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let pp = DrgPoRep::<Sha256Hasher, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+    assert_eq!(
+        pp.identifier(),
+        format!(
+            "schema-v1-drgporep::PublicParams{{graph: {}}}",
+            pp.graph.identifier()
+        )
+    );
+}
+
+#[test]
+fn test_drg_params_builder() {
+    let params = drg::DrgParams::builder()
+        .nodes(64)
+        .degree(BASE_DEGREE)
+        .porep_id([7; 32])
+        .build()
+        .expect("build failed");
+    assert_eq!(params.nodes, 64);
+    assert_eq!(params.degree, BASE_DEGREE);
+    assert_eq!(params.expansion_degree, 0);
+    assert_eq!(params.porep_id, [7; 32]);
+
+    let params = drg::DrgParams::builder()
+        .nodes(64)
+        .degree(BASE_DEGREE)
+        .expansion_degree(EXP_DEGREE)
+        .build()
+        .expect("build failed");
+    assert_eq!(params.expansion_degree, EXP_DEGREE);
+
+    // Missing nodes or degree default to zero, which is rejected.
+    assert!(drg::DrgParams::builder()
+        .degree(BASE_DEGREE)
+        .build()
+        .is_err());
+    assert!(drg::DrgParams::builder().nodes(64).build().is_err());
+    assert!(drg::DrgParams::builder()
+        .nodes(0)
+        .degree(BASE_DEGREE)
+        .build()
+        .is_err());
+    assert!(drg::DrgParams::builder()
+        .nodes(64)
+        .degree(0)
+        .build()
+        .is_err());
+    assert!(drg::DrgParams::builder()
+        .nodes(6)
+        .degree(6)
+        .build()
+        .is_err());
+    assert!(drg::DrgParams::builder()
+        .nodes(6)
+        .degree(7)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_drg_porep_verify_with_stored_parent_table() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+
+    // Replicating without asking for the parent table does not keep it.
+    let replica = TestReplica::<Sha256Hasher>::new(&mut rng, 16, 3);
+    assert!(replica.aux.parent_table.is_none());
+    assert!(replica
+        .aux
+        .write_parent_table(&replica.replica_path)
+        .is_err());
+
+    let replica = TestReplica::<Sha256Hasher>::with_parent_table(&mut rng, 16, 3);
+    let (pp, replica_path) = (&replica.pp, &replica.replica_path);
+    let commitment = replica
+        .aux
+        .write_parent_table(replica_path)
+        .expect("write_parent_table failed");
+    assert!(drg::parent_table_path(replica_path).exists());
+
+    let parent_table =
+        drg::read_parent_table(replica_path, &commitment).expect("read_parent_table failed");
+    assert_eq!(
+        parent_table,
+        pp.graph.parents_all().expect("parents_all failed")
+    );
+    assert!(drg::read_parent_table(replica_path, &[0; 32]).is_err());
+
+    let (pub_inputs, proof) = replica.prove(vec![2, 9, 15]);
+
+    let verify = |proof: &drg::Proof<Sha256Hasher>| {
+        let from_graph = DrgPoRep::<Sha256Hasher, _>::verify(pp, &pub_inputs, proof)
+            .expect("verification failed");
+        let from_table = DrgPoRep::<Sha256Hasher, _>::verify_with_parent_table(
+            pp,
+            &pub_inputs,
+            proof,
+            &parent_table,
+        )
+        .expect("verification failed");
+        assert_eq!(from_graph, from_table);
+        from_graph
+    };
+
+    assert!(verify(&proof));
+
+    let mut wrong_parent = proof.clone();
+    wrong_parent.replica_parents[0][0].0 += 1;
+    assert!(!verify(&wrong_parent));
+
+    let mut wrong_data = proof;
+    wrong_data.nodes[1].data = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    assert!(!verify(&wrong_data));
+}
+
+#[test]
+fn test_drg_porep_verify_and_extract() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let replica = TestReplica::<Sha256Hasher>::new(&mut rng, 16, 3);
+    let (pp, data) = (&replica.pp, &replica.data);
+    let (pub_inputs, proof) = replica.prove(vec![3, 8, 14]);
+
+    let (valid, unsealed) =
+        DrgPoRep::<Sha256Hasher, _>::verify_and_extract(pp, &pub_inputs, &proof)
+            .expect("verification failed");
+    assert!(valid);
+    assert_eq!(unsealed.len(), pub_inputs.challenges.len());
+    for ((challenge, bytes), expected) in unsealed.iter().zip(&pub_inputs.challenges) {
+        assert_eq!(challenge, expected);
+        assert_eq!(
+            &bytes[..],
+            &data[challenge * NODE_SIZE..(challenge + 1) * NODE_SIZE]
+        );
+    }
+
+    let mut tampered = proof;
+    tampered.replica_nodes[0].data = <Sha256Hasher as Hasher>::Domain::random(&mut rng);
+    let (valid, unsealed) =
+        DrgPoRep::<Sha256Hasher, _>::verify_and_extract(pp, &pub_inputs, &tampered)
+            .expect("verification failed");
+    assert!(!valid);
+    assert!(unsealed.is_empty());
+}
@@ -3,7 +3,8 @@ use std::fs::remove_file;
 use blstrs::Scalar as Fr;
 use ff::{Field, PrimeField};
 use filecoin_hashers::{
-    blake2s::Blake2sHasher, poseidon::PoseidonHasher, sha256::Sha256Hasher, Domain, Hasher,
+    blake2s::Blake2sHasher, poseidon::PoseidonHasher, sha256::Sha256Hasher, Domain, HashFunction,
+    Hasher,
 };
 use fr32::fr_into_bytes;
 use generic_array::typenum::{U0, U2, U4, U8};
@@ -24,8 +25,9 @@ use storage_proofs_core::{
 };
 use storage_proofs_porep::{
     stacked::{
-        LayerChallenges, PrivateInputs, PublicInputs, SetupParams, StackedBucketGraph, StackedDrg,
-        TemporaryAux, TemporaryAuxCache, BINARY_ARITY, EXP_DEGREE,
+        compute_comm_r, LayerChallenges, PrivateInputs, PublicInputs, SetupParams,
+        StackedBucketGraph, StackedDrg, Tau, TemporaryAux, TemporaryAuxCache, BINARY_ARITY,
+        EXP_DEGREE,
     },
     PoRep,
 };
@@ -579,3 +581,44 @@ fn test_generate_labels_aux(
 
     assert_eq!(expected_last_label.to_repr(), last_label.0);
 }
+
+#[test]
+fn test_compute_comm_r() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let comm_c = <PoseidonHasher as Hasher>::Domain::random(rng);
+    let comm_r_last = <PoseidonHasher as Hasher>::Domain::random(rng);
+    let comm_d = <Sha256Hasher as Hasher>::Domain::random(rng);
+
+    let comm_r = compute_comm_r::<PoseidonHasher>(&comm_c, &comm_r_last);
+    assert_eq!(
+        comm_r,
+        <PoseidonHasher as Hasher>::Function::hash2(&comm_c, &comm_r_last)
+    );
+    assert_ne!(
+        comm_r,
+        compute_comm_r::<PoseidonHasher>(&comm_r_last, &comm_c)
+    );
+
+    let tau = Tau::from_components::<PoseidonHasher>(comm_d, &comm_c, &comm_r_last);
+    assert_eq!(tau.comm_d, comm_d);
+    assert_eq!(tau.comm_r, comm_r);
+}
+
+#[test]
+fn test_tau_serialize_deserialize() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let tau = Tau {
+        comm_d: <Sha256Hasher as Hasher>::Domain::random(rng),
+        comm_r: <PoseidonHasher as Hasher>::Domain::random(rng),
+    };
+
+    let bytes = tau.serialize();
+    assert_eq!(&bytes[..32], AsRef::<[u8]>::as_ref(&tau.comm_d));
+    assert_eq!(&bytes[32..], AsRef::<[u8]>::as_ref(&tau.comm_r));
+
+    let deserialized: Tau<<PoseidonHasher as Hasher>::Domain, <Sha256Hasher as Hasher>::Domain> =
+        Tau::deserialize(&bytes).expect("deserialize failure");
+    assert_eq!(deserialized, tau);
+}